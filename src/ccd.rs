@@ -0,0 +1,726 @@
+//! Wrappers around the Connecting and Configuring Displays (CCD) API.
+//!
+//! https://docs.microsoft.com/en-us/windows-hardware/drivers/display/ccd-apis
+
+use std::{mem, ptr};
+
+use winapi::{
+    shared::{
+        basetsd::UINT32,
+        ntdef::LUID,
+        windef::RECTL,
+        winerror::{
+            ERROR_INSUFFICIENT_BUFFER, ERROR_INVALID_PARAMETER, ERROR_NOT_SUPPORTED, ERROR_SUCCESS,
+        },
+    },
+    um::{
+        wingdi::{
+            DISPLAYCONFIG_DEVICE_INFO_GET_ADVANCED_COLOR_INFO,
+            DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME, DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME,
+            DISPLAYCONFIG_DEVICE_INFO_HEADER, DISPLAYCONFIG_DEVICE_INFO_SET_ADVANCED_COLOR_STATE,
+            DISPLAYCONFIG_GET_ADVANCED_COLOR_INFO, DISPLAYCONFIG_MODE_INFO,
+            DISPLAYCONFIG_MODE_INFO_TYPE_DESKTOP_IMAGE, DISPLAYCONFIG_MODE_INFO_TYPE_SOURCE,
+            DISPLAYCONFIG_MODE_INFO_TYPE_TARGET, DISPLAYCONFIG_PATH_ACTIVE,
+            DISPLAYCONFIG_PATH_INFO, DISPLAYCONFIG_PATH_MODE_IDX_INVALID, DISPLAYCONFIG_RATIONAL,
+            DISPLAYCONFIG_ROTATION_IDENTITY, DISPLAYCONFIG_ROTATION_ROTATE180,
+            DISPLAYCONFIG_ROTATION_ROTATE270, DISPLAYCONFIG_ROTATION_ROTATE90,
+            DISPLAYCONFIG_SET_ADVANCED_COLOR_STATE, DISPLAYCONFIG_SOURCE_DEVICE_NAME,
+            DISPLAYCONFIG_TARGET_DEVICE_NAME, DISPLAYCONFIG_TOPOLOGY_ID, QDC_ALL_PATHS,
+            QDC_ONLY_ACTIVE_PATHS, SDC_ALLOW_CHANGES, SDC_APPLY, SDC_SAVE_TO_DATABASE,
+            SDC_TOPOLOGY_CLONE, SDC_TOPOLOGY_EXTEND, SDC_TOPOLOGY_EXTERNAL, SDC_TOPOLOGY_INTERNAL,
+            SDC_USE_DATABASE_CURRENT, SDC_USE_SUPPLIED_DISPLAY_CONFIG, SDC_VALIDATE,
+        },
+        winnt::LONG,
+    },
+};
+
+use crate::{
+    string_from_utf16_and_strip_null, DisplayAdapter, DisplayOrientation, Persistence, Point,
+    SetDisplaySettingsError,
+};
+
+// These aren't exposed by winapi 0.3
+#[link(name = "user32")]
+extern "system" {
+    fn GetDisplayConfigBufferSizes(
+        flags: UINT32,
+        numPathArrayElements: *mut UINT32,
+        numModeInfoArrayElements: *mut UINT32,
+    ) -> LONG;
+    fn QueryDisplayConfig(
+        flags: UINT32,
+        numPathArrayElements: *mut UINT32,
+        pathArray: *mut DISPLAYCONFIG_PATH_INFO,
+        numModeInfoArrayElements: *mut UINT32,
+        modeInfoArray: *mut DISPLAYCONFIG_MODE_INFO,
+        currentTopologyId: *mut DISPLAYCONFIG_TOPOLOGY_ID,
+    ) -> LONG;
+    fn DisplayConfigGetDeviceInfo(requestPacket: *mut DISPLAYCONFIG_DEVICE_INFO_HEADER) -> LONG;
+    fn DisplayConfigSetDeviceInfo(setPacket: *mut DISPLAYCONFIG_DEVICE_INFO_HEADER) -> LONG;
+    fn SetDisplayConfig(
+        numPathArrayElements: UINT32,
+        pathArray: *mut DISPLAYCONFIG_PATH_INFO,
+        numModeInfoArrayElements: UINT32,
+        modeInfoArray: *mut DISPLAYCONFIG_MODE_INFO,
+        flags: UINT32,
+    ) -> LONG;
+}
+
+/// The display topologies offered by the Win+P menu.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Topology {
+    /// Only the internal display, e.g. a laptop's own panel.
+    Internal,
+    /// Only the external display(s).
+    External,
+    /// The same desktop on all displays.
+    Clone,
+    /// The desktop extended across all displays.
+    Extend,
+}
+
+impl Topology {
+    fn as_raw(self) -> UINT32 {
+        match self {
+            Self::Internal => SDC_TOPOLOGY_INTERNAL,
+            Self::External => SDC_TOPOLOGY_EXTERNAL,
+            Self::Clone => SDC_TOPOLOGY_CLONE,
+            Self::Extend => SDC_TOPOLOGY_EXTEND,
+        }
+    }
+}
+
+/// Switches to one of the topologies offered by the Win+P menu.
+pub fn set_topology(topology: Topology) -> Result<(), SetDisplaySettingsError> {
+    set_without_paths(SDC_APPLY | topology.as_raw())
+}
+
+/// Like `set_topology`, but also saves the resulting configuration to the persistence database, so
+/// that Windows restores it the next time the same set of displays is connected.
+pub fn set_topology_and_save(topology: Topology) -> Result<(), SetDisplaySettingsError> {
+    set_without_paths(SDC_APPLY | SDC_SAVE_TO_DATABASE | topology.as_raw())
+}
+
+/// Applies the configuration which the persistence database holds for the currently connected set
+/// of displays.
+pub fn apply_database_config() -> Result<(), SetDisplaySettingsError> {
+    set_without_paths(SDC_APPLY | SDC_USE_DATABASE_CURRENT)
+}
+
+fn set_without_paths(flags: UINT32) -> Result<(), SetDisplaySettingsError> {
+    let ret = unsafe { SetDisplayConfig(0, ptr::null_mut(), 0, ptr::null_mut(), flags) };
+    log::debug!("SetDisplayConfig with flags {:#x} returned {}", flags, ret);
+
+    match ret {
+        ret if ret == ERROR_SUCCESS as LONG => Ok(()),
+        ret => Err(error_from_win32(ret)),
+    }
+}
+
+/// Mirrors `DISPLAYCONFIG_PATH_INFO`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PathInfo {
+    pub source_info: PathSourceInfo,
+    pub target_info: PathTargetInfo,
+    pub flags: u32,
+}
+
+/// Mirrors `DISPLAYCONFIG_PATH_SOURCE_INFO`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PathSourceInfo {
+    pub adapter_id: AdapterId,
+    pub id: u32,
+    pub mode_info_idx: u32,
+    pub status_flags: u32,
+}
+
+/// Mirrors `DISPLAYCONFIG_PATH_TARGET_INFO`.
+///
+/// The enum fields hold the raw `DISPLAYCONFIG_*` values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PathTargetInfo {
+    pub adapter_id: AdapterId,
+    pub id: u32,
+    pub mode_info_idx: u32,
+    pub output_technology: u32,
+    pub rotation: u32,
+    pub scaling: u32,
+    pub refresh_rate: Rational,
+    pub scan_line_ordering: u32,
+    pub target_available: bool,
+    pub status_flags: u32,
+}
+
+/// Mirrors `DISPLAYCONFIG_MODE_INFO`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ModeInfo {
+    pub id: u32,
+    pub adapter_id: AdapterId,
+    pub mode: Mode,
+}
+
+/// The union in `DISPLAYCONFIG_MODE_INFO`, tagged by its `infoType`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Source(SourceMode),
+    Target(TargetMode),
+    DesktopImage(DesktopImageInfo),
+}
+
+/// Mirrors `DISPLAYCONFIG_SOURCE_MODE`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourceMode {
+    pub width: u32,
+    pub height: u32,
+    pub pixel_format: u32,
+    pub position: Point,
+}
+
+/// Mirrors `DISPLAYCONFIG_TARGET_MODE` and the `DISPLAYCONFIG_VIDEO_SIGNAL_INFO` inside it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TargetMode {
+    pub pixel_rate: u64,
+    pub h_sync_freq: Rational,
+    pub v_sync_freq: Rational,
+    pub active_size: (u32, u32),
+    pub total_size: (u32, u32),
+    pub video_standard: u32,
+    pub scan_line_ordering: u32,
+}
+
+/// Mirrors `DISPLAYCONFIG_DESKTOP_IMAGE_INFO`. Rectangles are `(top_left, bottom_right)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DesktopImageInfo {
+    pub path_source_size: Point,
+    pub desktop_image_region: (Point, Point),
+    pub desktop_image_clip: (Point, Point),
+}
+
+/// The locally unique identifier (`LUID`) of a graphics adapter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AdapterId {
+    pub low_part: u32,
+    pub high_part: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rational {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+/// Retrieves the active paths and their modes.
+///
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-querydisplayconfig
+pub fn query_display_config() -> Option<(Vec<PathInfo>, Vec<ModeInfo>)> {
+    let (paths, modes) = query_raw(QDC_ONLY_ACTIVE_PATHS)?;
+
+    Some((
+        paths.iter().map(PathInfo::from).collect(),
+        modes.iter().filter_map(ModeInfo::from_raw).collect(),
+    ))
+}
+
+/// A monitor as seen by the CCD API, i.e. a path target.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisplayTarget {
+    pub adapter_id: AdapterId,
+    /// The target ID, which is unique per adapter.
+    pub id: u32,
+    /// The monitor's friendly name, e.g. from its EDID. Empty for some built-in panels.
+    pub name: String,
+    /// The monitor's device interface path, as in `Monitor::interface_path`.
+    pub device_path: String,
+    /// Whether the monitor is part of an active path, i.e. whether it's showing the desktop.
+    pub active: bool,
+}
+
+impl DisplayTarget {
+    /// Turns on a connected but inactive monitor by adding a path to it to the active
+    /// configuration. The monitor extends the desktop, and Windows picks its mode and position.
+    ///
+    /// Fails with `SetDisplaySettingsError::BadParam` if the monitor isn't connected anymore, and
+    /// with `SetDisplaySettingsError::NoFreeSource` if its adapter can't drive another display.
+    pub fn enable(&self) -> Result<(), SetDisplaySettingsError> {
+        let (mut paths, mut modes) =
+            query_raw(QDC_ONLY_ACTIVE_PATHS).ok_or(SetDisplaySettingsError::Failed)?;
+        let (all_paths, _) = query_raw(QDC_ALL_PATHS).ok_or(SetDisplaySettingsError::Failed)?;
+
+        let is_target = |path: &DISPLAYCONFIG_PATH_INFO| {
+            AdapterId::from(path.targetInfo.adapterId) == self.adapter_id
+                && path.targetInfo.id == self.id
+        };
+        if paths.iter().any(is_target) {
+            return Ok(());
+        }
+
+        let candidates: Vec<_> = all_paths
+            .into_iter()
+            .filter(|path| is_target(path) && path.targetInfo.targetAvailable != 0)
+            .collect();
+        if candidates.is_empty() {
+            return Err(SetDisplaySettingsError::BadParam);
+        }
+
+        // Every source can only drive one path, unless the desktop is cloned.
+        let source_in_use = |candidate: &DISPLAYCONFIG_PATH_INFO| {
+            paths.iter().any(|path| {
+                AdapterId::from(path.sourceInfo.adapterId)
+                    == AdapterId::from(candidate.sourceInfo.adapterId)
+                    && path.sourceInfo.id == candidate.sourceInfo.id
+            })
+        };
+        let mut path = *candidates
+            .iter()
+            .find(|candidate| !source_in_use(candidate))
+            .ok_or(SetDisplaySettingsError::NoFreeSource)?;
+
+        path.flags |= DISPLAYCONFIG_PATH_ACTIVE;
+        path.sourceInfo.modeInfoIdx = DISPLAYCONFIG_PATH_MODE_IDX_INVALID;
+        path.targetInfo.modeInfoIdx = DISPLAYCONFIG_PATH_MODE_IDX_INVALID;
+        paths.push(path);
+
+        let flags = SDC_USE_SUPPLIED_DISPLAY_CONFIG | SDC_ALLOW_CHANGES | SDC_SAVE_TO_DATABASE;
+        match set_raw(&mut paths, &mut modes, flags | SDC_APPLY) {
+            ret if ret == ERROR_SUCCESS as LONG => Ok(()),
+            ret => Err(error_from_win32(ret)),
+        }
+    }
+}
+
+/// Lists the connected monitors, including the ones which are connected but disabled if
+/// `include_inactive` is set.
+///
+/// Unlike `Monitors`, this also finds monitors which aren't attached to any adapter.
+pub fn all_monitors(include_inactive: bool) -> Option<Vec<DisplayTarget>> {
+    let flags = if include_inactive {
+        QDC_ALL_PATHS
+    } else {
+        QDC_ONLY_ACTIVE_PATHS
+    };
+    let (paths, _) = query_raw(flags)?;
+
+    // With `QDC_ALL_PATHS`, there's a path for every possible source of every target.
+    let mut targets: Vec<DisplayTarget> = Vec::new();
+    for path in &paths {
+        let target = &path.targetInfo;
+        if target.targetAvailable == 0 {
+            continue;
+        }
+
+        let adapter_id = AdapterId::from(target.adapterId);
+        let active = path.flags & DISPLAYCONFIG_PATH_ACTIVE != 0;
+        if let Some(existing) = targets
+            .iter_mut()
+            .find(|existing| existing.adapter_id == adapter_id && existing.id == target.id)
+        {
+            existing.active |= active;
+            continue;
+        }
+
+        let (name, device_path) = target_device_name(path).unwrap_or_default();
+        targets.push(DisplayTarget {
+            adapter_id,
+            id: target.id,
+            name,
+            device_path,
+            active,
+        });
+    }
+
+    Some(targets)
+}
+
+impl From<&DISPLAYCONFIG_PATH_INFO> for PathInfo {
+    fn from(from: &DISPLAYCONFIG_PATH_INFO) -> Self {
+        let source = &from.sourceInfo;
+        let target = &from.targetInfo;
+
+        Self {
+            source_info: PathSourceInfo {
+                adapter_id: source.adapterId.into(),
+                id: source.id,
+                mode_info_idx: source.modeInfoIdx,
+                status_flags: source.statusFlags,
+            },
+            target_info: PathTargetInfo {
+                adapter_id: target.adapterId.into(),
+                id: target.id,
+                mode_info_idx: target.modeInfoIdx,
+                output_technology: target.outputTechnology,
+                rotation: target.rotation,
+                scaling: target.scaling,
+                refresh_rate: target.refreshRate.into(),
+                scan_line_ordering: target.scanLineOrdering,
+                target_available: target.targetAvailable != 0,
+                status_flags: target.statusFlags,
+            },
+            flags: from.flags,
+        }
+    }
+}
+
+impl ModeInfo {
+    fn from_raw(raw: &DISPLAYCONFIG_MODE_INFO) -> Option<Self> {
+        let mode = match raw.infoType {
+            DISPLAYCONFIG_MODE_INFO_TYPE_SOURCE => {
+                let mode = unsafe { raw.u.sourceMode() };
+                Mode::Source(SourceMode {
+                    width: mode.width,
+                    height: mode.height,
+                    pixel_format: mode.pixelFormat,
+                    position: mode.position.into(),
+                })
+            }
+            DISPLAYCONFIG_MODE_INFO_TYPE_TARGET => {
+                let signal = unsafe { raw.u.targetMode() }.targetVideoSignalInfo;
+                Mode::Target(TargetMode {
+                    pixel_rate: signal.pixelRate,
+                    h_sync_freq: signal.hSyncFreq.into(),
+                    v_sync_freq: signal.vSyncFreq.into(),
+                    active_size: (signal.activeSize.cx, signal.activeSize.cy),
+                    total_size: (signal.totalSize.cx, signal.totalSize.cy),
+                    video_standard: unsafe { *signal.u.videoStandard() },
+                    scan_line_ordering: signal.scanLineOrdering,
+                })
+            }
+            DISPLAYCONFIG_MODE_INFO_TYPE_DESKTOP_IMAGE => {
+                let info = unsafe { raw.u.desktopImageInfo() };
+                Mode::DesktopImage(DesktopImageInfo {
+                    path_source_size: info.PathSourceSize.into(),
+                    desktop_image_region: rect_from_rectl(&info.DesktopImageRegion),
+                    desktop_image_clip: rect_from_rectl(&info.DesktopImageClip),
+                })
+            }
+            _ => return None,
+        };
+
+        Some(Self {
+            id: raw.id,
+            adapter_id: raw.adapterId.into(),
+            mode,
+        })
+    }
+}
+
+impl From<LUID> for AdapterId {
+    fn from(from: LUID) -> Self {
+        Self {
+            low_part: from.LowPart,
+            high_part: from.HighPart,
+        }
+    }
+}
+
+impl From<DISPLAYCONFIG_RATIONAL> for Rational {
+    fn from(from: DISPLAYCONFIG_RATIONAL) -> Self {
+        Self {
+            numerator: from.Numerator,
+            denominator: from.Denominator,
+        }
+    }
+}
+
+fn rect_from_rectl(rect: &RECTL) -> (Point, Point) {
+    (
+        Point {
+            x: rect.left,
+            y: rect.top,
+        },
+        Point {
+            x: rect.right,
+            y: rect.bottom,
+        },
+    )
+}
+
+impl DisplayAdapter {
+    /// Whether the monitor supports advanced color (HDR).
+    pub fn hdr_capable(&self) -> Option<bool> {
+        let path = find_path(&self.name)?;
+
+        let mut info: DISPLAYCONFIG_GET_ADVANCED_COLOR_INFO = unsafe { mem::zeroed() };
+        info.header._type = DISPLAYCONFIG_DEVICE_INFO_GET_ADVANCED_COLOR_INFO;
+        info.header.size = mem::size_of::<DISPLAYCONFIG_GET_ADVANCED_COLOR_INFO>() as u32;
+        info.header.adapterId = path.targetInfo.adapterId;
+        info.header.id = path.targetInfo.id;
+
+        if unsafe { DisplayConfigGetDeviceInfo(&mut info.header) } != ERROR_SUCCESS as LONG {
+            return None;
+        }
+
+        Some(info.advancedColorSupported() != 0)
+    }
+
+    /// The exact refresh rate of the display as a `(numerator, denominator)` pair.
+    ///
+    /// Unlike `DisplayDeviceInfo::frequency`, this can represent fractional rates like 59.94 Hz.
+    pub fn refresh_rate_exact(&self) -> Option<(u32, u32)> {
+        let path = find_path(&self.name)?;
+        let rate = path.targetInfo.refreshRate;
+
+        if rate.Denominator == 0 {
+            None
+        } else {
+            Some((rate.Numerator, rate.Denominator))
+        }
+    }
+
+    /// Sets an exact refresh rate, such as 59.94 Hz (`60000 / 1001`), which can't be expressed
+    /// through `ChangeDisplaySettingsExW`.
+    ///
+    /// Returns `SetDisplaySettingsError::BadMode` if the display doesn't support exactly this rate
+    /// at its current resolution. The previous configuration is restored in that case.
+    ///
+    /// The CCD API can't defer changes, so `Persistence::Deferred` fails with
+    /// `SetDisplaySettingsError::NotSupported`.
+    pub fn set_refresh_rate_exact(
+        &self,
+        numerator: u32,
+        denominator: u32,
+        persistence: Persistence,
+    ) -> Result<(), SetDisplaySettingsError> {
+        if denominator == 0 {
+            return Err(SetDisplaySettingsError::BadParam);
+        }
+        let save = match persistence {
+            Persistence::Dynamic => 0,
+            Persistence::Persistent => SDC_SAVE_TO_DATABASE,
+            Persistence::Deferred => return Err(SetDisplaySettingsError::NotSupported),
+        };
+
+        let (mut paths, mut modes) =
+            query_raw(QDC_ONLY_ACTIVE_PATHS).ok_or(SetDisplaySettingsError::Failed)?;
+        let (mut original_paths, mut original_modes) = (paths.clone(), modes.clone());
+
+        let path = paths
+            .iter_mut()
+            .find(|path| source_gdi_name(path).as_deref() == Some(self.name.as_str()))
+            .ok_or(SetDisplaySettingsError::Failed)?;
+
+        // With the target mode left out, the refresh rate from the path is used to pick one
+        path.targetInfo.refreshRate = DISPLAYCONFIG_RATIONAL {
+            Numerator: numerator,
+            Denominator: denominator,
+        };
+        path.targetInfo.modeInfoIdx = DISPLAYCONFIG_PATH_MODE_IDX_INVALID;
+
+        let flags = SDC_USE_SUPPLIED_DISPLAY_CONFIG | SDC_ALLOW_CHANGES;
+        if set_raw(&mut paths, &mut modes, flags | SDC_VALIDATE) != ERROR_SUCCESS as LONG {
+            return Err(SetDisplaySettingsError::BadMode);
+        }
+        match set_raw(&mut paths, &mut modes, flags | SDC_APPLY | save) {
+            ret if ret == ERROR_SUCCESS as LONG => {}
+            ret => return Err(error_from_win32(ret)),
+        }
+
+        // `SDC_ALLOW_CHANGES` lets the driver settle for a different rate
+        let exact = match self.refresh_rate_exact() {
+            Some((n, d)) => n as u64 * denominator as u64 == numerator as u64 * d as u64,
+            None => false,
+        };
+        if !exact {
+            let ret = set_raw(
+                &mut original_paths,
+                &mut original_modes,
+                SDC_USE_SUPPLIED_DISPLAY_CONFIG | SDC_APPLY | save,
+            );
+            if ret != ERROR_SUCCESS as LONG {
+                log::warn!(
+                    "failed to roll back the refresh rate of {}: SetDisplayConfig returned {}",
+                    self.name,
+                    ret
+                );
+            }
+            return Err(SetDisplaySettingsError::BadMode);
+        }
+
+        Ok(())
+    }
+
+    /// Like `supported_orientations`, but checked through the CCD API.
+    ///
+    /// CCD doesn't report which rotations a target supports, so each one is checked with
+    /// `SDC_VALIDATE`, which never touches the display and is faster than `CDS_TEST`. Falls back to
+    /// `supported_orientations` if the display has no active CCD path.
+    pub fn supported_rotations(&self) -> Vec<DisplayOrientation> {
+        let (mut paths, mut modes) = match query_raw(QDC_ONLY_ACTIVE_PATHS) {
+            Some(config) => config,
+            None => return self.supported_orientations(),
+        };
+        let index = match paths
+            .iter()
+            .position(|path| source_gdi_name(path).as_deref() == Some(self.name.as_str()))
+        {
+            Some(index) => index,
+            None => return self.supported_orientations(),
+        };
+
+        [
+            (DisplayOrientation::Default, DISPLAYCONFIG_ROTATION_IDENTITY),
+            (
+                DisplayOrientation::Rotate90,
+                DISPLAYCONFIG_ROTATION_ROTATE90,
+            ),
+            (
+                DisplayOrientation::Rotate180,
+                DISPLAYCONFIG_ROTATION_ROTATE180,
+            ),
+            (
+                DisplayOrientation::Rotate270,
+                DISPLAYCONFIG_ROTATION_ROTATE270,
+            ),
+        ]
+        .iter()
+        .filter(|&&(_, rotation)| {
+            paths[index].targetInfo.rotation = rotation;
+            let flags = SDC_VALIDATE | SDC_USE_SUPPLIED_DISPLAY_CONFIG | SDC_ALLOW_CHANGES;
+            set_raw(&mut paths, &mut modes, flags) == ERROR_SUCCESS as LONG
+        })
+        .map(|&(orientation, _)| orientation)
+        .collect()
+    }
+
+    /// Turns advanced color (HDR) on or off.
+    ///
+    /// Returns `SetDisplaySettingsError::NotSupported` if the monitor isn't HDR capable.
+    pub fn set_hdr(&self, enabled: bool) -> Result<(), SetDisplaySettingsError> {
+        match self.hdr_capable() {
+            Some(true) => {}
+            Some(false) => return Err(SetDisplaySettingsError::NotSupported),
+            None => return Err(SetDisplaySettingsError::Failed),
+        }
+        let path = find_path(&self.name).ok_or(SetDisplaySettingsError::Failed)?;
+
+        let mut state: DISPLAYCONFIG_SET_ADVANCED_COLOR_STATE = unsafe { mem::zeroed() };
+        state.header._type = DISPLAYCONFIG_DEVICE_INFO_SET_ADVANCED_COLOR_STATE;
+        state.header.size = mem::size_of::<DISPLAYCONFIG_SET_ADVANCED_COLOR_STATE>() as u32;
+        state.header.adapterId = path.targetInfo.adapterId;
+        state.header.id = path.targetInfo.id;
+        state.set_enableAdvancedColor(enabled as u32);
+
+        match unsafe { DisplayConfigSetDeviceInfo(&mut state.header) } {
+            ret if ret == ERROR_SUCCESS as LONG => Ok(()),
+            ret => Err(error_from_win32(ret)),
+        }
+    }
+}
+
+fn error_from_win32(ret: LONG) -> SetDisplaySettingsError {
+    match ret as u32 {
+        ERROR_INVALID_PARAMETER => SetDisplaySettingsError::BadParam,
+        ERROR_NOT_SUPPORTED => SetDisplaySettingsError::NotSupported,
+        _ => SetDisplaySettingsError::Failed,
+    }
+}
+
+fn query_raw(
+    flags: UINT32,
+) -> Option<(Vec<DISPLAYCONFIG_PATH_INFO>, Vec<DISPLAYCONFIG_MODE_INFO>)> {
+    loop {
+        let (mut num_paths, mut num_modes) = (0, 0);
+        let ret = unsafe { GetDisplayConfigBufferSizes(flags, &mut num_paths, &mut num_modes) };
+        if ret != ERROR_SUCCESS as LONG {
+            log::debug!("GetDisplayConfigBufferSizes returned {}", ret);
+            return None;
+        }
+
+        let mut paths: Vec<DISPLAYCONFIG_PATH_INFO> =
+            vec![unsafe { mem::zeroed() }; num_paths as usize];
+        let mut modes: Vec<DISPLAYCONFIG_MODE_INFO> =
+            vec![unsafe { mem::zeroed() }; num_modes as usize];
+
+        let ret = unsafe {
+            QueryDisplayConfig(
+                flags,
+                &mut num_paths,
+                paths.as_mut_ptr(),
+                &mut num_modes,
+                modes.as_mut_ptr(),
+                ptr::null_mut(),
+            )
+        };
+
+        match ret as u32 {
+            ERROR_SUCCESS => {
+                paths.truncate(num_paths as usize);
+                modes.truncate(num_modes as usize);
+                return Some((paths, modes));
+            }
+            // The configuration changed between the two calls
+            ERROR_INSUFFICIENT_BUFFER => continue,
+            _ => {
+                log::debug!("QueryDisplayConfig returned {}", ret);
+                return None;
+            }
+        }
+    }
+}
+
+fn set_raw(
+    paths: &mut [DISPLAYCONFIG_PATH_INFO],
+    modes: &mut [DISPLAYCONFIG_MODE_INFO],
+    flags: UINT32,
+) -> LONG {
+    let ret = unsafe {
+        SetDisplayConfig(
+            paths.len() as u32,
+            paths.as_mut_ptr(),
+            modes.len() as u32,
+            modes.as_mut_ptr(),
+            flags,
+        )
+    };
+    log::debug!(
+        "SetDisplayConfig with {} paths, {} modes and flags {:#x} returned {}",
+        paths.len(),
+        modes.len(),
+        flags,
+        ret
+    );
+
+    ret
+}
+
+fn source_gdi_name(path: &DISPLAYCONFIG_PATH_INFO) -> Option<String> {
+    let mut source_name: DISPLAYCONFIG_SOURCE_DEVICE_NAME = unsafe { mem::zeroed() };
+    source_name.header._type = DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME;
+    source_name.header.size = mem::size_of::<DISPLAYCONFIG_SOURCE_DEVICE_NAME>() as u32;
+    source_name.header.adapterId = path.sourceInfo.adapterId;
+    source_name.header.id = path.sourceInfo.id;
+
+    if unsafe { DisplayConfigGetDeviceInfo(&mut source_name.header) } != ERROR_SUCCESS as LONG {
+        return None;
+    }
+
+    Some(string_from_utf16_and_strip_null(
+        &source_name.viewGdiDeviceName,
+    ))
+}
+
+/// The friendly name and device path of the path's target.
+fn target_device_name(path: &DISPLAYCONFIG_PATH_INFO) -> Option<(String, String)> {
+    let mut target_name: DISPLAYCONFIG_TARGET_DEVICE_NAME = unsafe { mem::zeroed() };
+    target_name.header._type = DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME;
+    target_name.header.size = mem::size_of::<DISPLAYCONFIG_TARGET_DEVICE_NAME>() as u32;
+    target_name.header.adapterId = path.targetInfo.adapterId;
+    target_name.header.id = path.targetInfo.id;
+
+    if unsafe { DisplayConfigGetDeviceInfo(&mut target_name.header) } != ERROR_SUCCESS as LONG {
+        return None;
+    }
+
+    Some((
+        string_from_utf16_and_strip_null(&target_name.monitorFriendlyDeviceName),
+        string_from_utf16_and_strip_null(&target_name.monitorDevicePath),
+    ))
+}
+
+/// Finds the active path whose source is the GDI device `gdi_name`, e.g. `\\.\DISPLAY1`.
+fn find_path(gdi_name: &str) -> Option<DISPLAYCONFIG_PATH_INFO> {
+    let (paths, _) = query_raw(QDC_ONLY_ACTIVE_PATHS)?;
+
+    paths
+        .into_iter()
+        .find(|path| source_gdi_name(path).as_deref() == Some(gdi_name))
+}
@@ -0,0 +1,281 @@
+use std::{mem, sync::mpsc, thread, time::Duration};
+
+use crate::{
+    ApplyResult, DisplayAdapter, DisplayAdapters, DisplayDeviceInfo, DisplayOrientation, DmFields,
+    Persistence, Point, SetDisplaySettingsError,
+};
+
+impl DisplayAdapter {
+    /// Starts a change of several settings at once, which is applied with a single
+    /// `ChangeDisplaySettingsExW` call.
+    pub fn change(&self) -> SettingsChange<'_> {
+        SettingsChange {
+            adapter: self,
+            resolution: None,
+            frequency: None,
+            bits_per_pel: None,
+            orientation: None,
+            position: None,
+            persistence: Persistence::default(),
+            force: false,
+            verify: false,
+        }
+    }
+
+    /// Applies the settings which are set in `config` with a single `ChangeDisplaySettingsExW`
+    /// call, like `change` does. Use `change().config(config)` for more control over the change.
+    pub fn apply_config(
+        &self,
+        config: &DisplayConfig,
+        persistence: Persistence,
+    ) -> Result<ApplyResult, SetDisplaySettingsError> {
+        self.change()
+            .config(config)
+            .persistence(persistence)
+            .apply()
+    }
+
+    /// Applies `config` like `apply_config`, but reverts to the previous settings after `timeout`
+    /// or when the returned guard is dropped, unless `TemporaryChange::keep` is called first.
+    ///
+    /// This is the "Keep these display settings?" prompt of the Windows settings app. The change is
+    /// never saved to the registry, even if it's kept.
+    pub fn apply_temporarily(
+        &self,
+        config: &DisplayConfig,
+        timeout: Duration,
+    ) -> Result<TemporaryChange, SetDisplaySettingsError> {
+        let mut previous =
+            DisplayDeviceInfo::try_get_raw(self).ok_or(SetDisplaySettingsError::Failed)?;
+        previous.dmFields = (DmFields::POSITION
+            | DmFields::PELSWIDTH
+            | DmFields::PELSHEIGHT
+            | DmFields::BITSPERPEL
+            | DmFields::DISPLAYFREQUENCY
+            | DmFields::DISPLAYORIENTATION)
+            .bits();
+
+        self.apply_config(config, Persistence::Dynamic)?
+            .into_result()?;
+
+        let name = self.name.clone();
+        let (keep_sender, keep_receiver) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            // The sender is dropped without sending if the guard is dropped.
+            if keep_receiver.recv_timeout(timeout).is_ok() {
+                return;
+            }
+
+            let result = DisplayAdapters::new()
+                .ok_or(SetDisplaySettingsError::Failed)
+                .and_then(|adapters| {
+                    let adapter = adapters
+                        .iter()
+                        .find(|adapter| adapter.name == name)
+                        .ok_or(SetDisplaySettingsError::BadParam)?;
+                    adapter.change_settings(&mut previous, 0)?.into_result()
+                });
+            if let Err(error) = result {
+                log::warn!("failed to revert the settings of {}: {:?}", name, error);
+            }
+        });
+
+        Ok(TemporaryChange {
+            keep_sender: Some(keep_sender),
+            thread: Some(thread),
+        })
+    }
+}
+
+/// A settings change which is reverted when dropped, as returned by
+/// `DisplayAdapter::apply_temporarily`. Dropping it blocks until the settings are reverted.
+pub struct TemporaryChange {
+    keep_sender: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl TemporaryChange {
+    /// Keeps the new settings. Does nothing if they have already been reverted.
+    pub fn keep(mut self) {
+        if let Some(sender) = self.keep_sender.take() {
+            let _ = sender.send(());
+        }
+    }
+}
+
+impl Drop for TemporaryChange {
+    fn drop(&mut self) {
+        self.keep_sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The settings of a display, e.g. as read from a configuration file. Settings which are `None` are
+/// left as they are by `DisplayAdapter::apply_config`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct DisplayConfig {
+    pub resolution: Option<(u32, u32)>,
+    pub refresh_rate: Option<u32>,
+    pub bits_per_pel: Option<u32>,
+    pub orientation: Option<DisplayOrientation>,
+    pub position: Option<Point>,
+}
+
+/// A builder for a settings change on a single adapter. Settings which aren't set are left as they
+/// are.
+pub struct SettingsChange<'a> {
+    adapter: &'a DisplayAdapter,
+    resolution: Option<(u32, u32)>,
+    frequency: Option<u32>,
+    bits_per_pel: Option<u32>,
+    orientation: Option<DisplayOrientation>,
+    position: Option<Point>,
+    persistence: Persistence,
+    force: bool,
+    verify: bool,
+}
+
+impl SettingsChange<'_> {
+    pub fn resolution(mut self, width: u32, height: u32) -> Self {
+        self.resolution = Some((width, height));
+        self
+    }
+
+    pub fn frequency(mut self, frequency: u32) -> Self {
+        self.frequency = Some(frequency);
+        self
+    }
+
+    pub fn bits_per_pel(mut self, bits_per_pel: u32) -> Self {
+        self.bits_per_pel = Some(bits_per_pel);
+        self
+    }
+
+    /// If no resolution is given, the width and height are swapped as needed to match the new
+    /// orientation.
+    pub fn orientation(mut self, orientation: DisplayOrientation) -> Self {
+        self.orientation = Some(orientation);
+        self
+    }
+
+    pub fn position(mut self, position: Point) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    /// Changes the settings which are set in `config`.
+    pub fn config(mut self, config: &DisplayConfig) -> Self {
+        if let Some(resolution) = config.resolution {
+            self.resolution = Some(resolution);
+        }
+        if let Some(frequency) = config.refresh_rate {
+            self.frequency = Some(frequency);
+        }
+        if let Some(bits_per_pel) = config.bits_per_pel {
+            self.bits_per_pel = Some(bits_per_pel);
+        }
+        if let Some(orientation) = config.orientation {
+            self.orientation = Some(orientation);
+        }
+        if let Some(position) = config.position {
+            self.position = Some(position);
+        }
+        self
+    }
+
+    /// Shorthand for `persistence(Persistence::Deferred)`. `apply` then returns
+    /// `ApplyResult::SavedToRegistry`, and the change takes effect with the next call to
+    /// `apply_pending`.
+    pub fn deferred(self) -> Self {
+        self.persistence(Persistence::Deferred)
+    }
+
+    /// Whether the change is saved to the registry, and whether it's applied right away. Defaults
+    /// to `Persistence::Dynamic`.
+    pub fn persistence(mut self, persistence: Persistence) -> Self {
+        self.persistence = persistence;
+        self
+    }
+
+    /// Apply the change even if the settings are already in effect. By default, `apply` then
+    /// returns `ApplyResult::Applied` without calling `ChangeDisplaySettingsExW`, since redundant
+    /// changes can still make the display flicker.
+    ///
+    /// This also allows a zero resolution to detach the last active display, which otherwise
+    /// fails with `SetDisplaySettingsError::WouldDisableLastDisplay`.
+    pub fn force(mut self) -> Self {
+        self.force = true;
+        self
+    }
+
+    /// Check the settings in effect after applying the change. If the driver picked a different
+    /// mode or position than requested, `apply` returns `ApplyResult::Substituted`.
+    pub fn verify(mut self) -> Self {
+        self.verify = true;
+        self
+    }
+
+    pub fn apply(self) -> Result<ApplyResult, SetDisplaySettingsError> {
+        let mut devmode = DisplayDeviceInfo::get_raw(self.adapter);
+        let mut fields = DmFields::empty();
+
+        if let Some(orientation) = self.orientation {
+            let current = unsafe { devmode.u1.s2().dmDisplayOrientation };
+            if self.resolution.is_none()
+                && matches!(
+                    DisplayOrientation::from_raw(current),
+                    Some(current) if current.is_90_or_270_relative(orientation)
+                )
+            {
+                mem::swap(&mut devmode.dmPelsWidth, &mut devmode.dmPelsHeight);
+                fields.insert(DmFields::PELSWIDTH | DmFields::PELSHEIGHT);
+            }
+
+            fields.insert(DmFields::DISPLAYORIENTATION);
+            unsafe { devmode.u1.s2_mut() }.dmDisplayOrientation = orientation.as_raw();
+        }
+        if let Some((width, height)) = self.resolution {
+            fields.insert(DmFields::PELSWIDTH | DmFields::PELSHEIGHT);
+            devmode.dmPelsWidth = width;
+            devmode.dmPelsHeight = height;
+        }
+        if let Some(frequency) = self.frequency {
+            fields.insert(DmFields::DISPLAYFREQUENCY);
+            devmode.dmDisplayFrequency = frequency;
+        }
+        if let Some(bits_per_pel) = self.bits_per_pel {
+            fields.insert(DmFields::BITSPERPEL);
+            devmode.dmBitsPerPel = bits_per_pel;
+        }
+        if let Some(position) = self.position {
+            fields.insert(DmFields::POSITION);
+            unsafe { devmode.u1.s2_mut() }.dmPosition = position.into();
+        }
+
+        devmode.dmFields = fields.bits();
+        if !self.force && self.adapter.is_in_effect(&devmode, self.persistence) {
+            return Ok(ApplyResult::Applied);
+        }
+
+        let flags = self.persistence.flags();
+
+        let result = if self.force {
+            self.adapter
+                .change_settings_unchecked(&mut devmode, flags)?
+        } else {
+            self.adapter.change_settings(&mut devmode, flags)?
+        };
+        if self.verify && result == ApplyResult::Applied && !self.adapter.is_current(&devmode) {
+            if let Some(mode) = self.adapter.current_mode() {
+                log::debug!("{} was set to {:?} instead", self.adapter.name, mode);
+                return Ok(ApplyResult::Substituted(mode));
+            }
+        }
+
+        Ok(result)
+    }
+}
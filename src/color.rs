@@ -0,0 +1,232 @@
+//! ICC color profiles of displays.
+//!
+//! https://docs.microsoft.com/en-us/windows/win32/wcs/windows-color-system
+
+use std::{fs::File, io::Read, path::Path, ptr};
+
+use winapi::{
+    shared::minwindef::{BOOL, DWORD, TRUE},
+    um::winnt::{LPCWSTR, LPWSTR},
+};
+
+use crate::{string_from_utf16_and_strip_null, to_wide, DisplayAdapter};
+
+// These aren't exposed by winapi 0.3
+const WCS_PROFILE_MANAGEMENT_SCOPE_CURRENT_USER: DWORD = 1;
+const CPT_ICC: DWORD = 0;
+const CPST_NONE: DWORD = 4;
+// 'mntr'
+const CLASS_MONITOR: DWORD = 0x6d6e_7472;
+
+#[link(name = "mscms")]
+extern "system" {
+    fn WcsGetDefaultColorProfileSize(
+        scope: DWORD,
+        pDeviceName: LPCWSTR,
+        cptColorProfileType: DWORD,
+        cpstColorProfileSubType: DWORD,
+        dwProfileID: DWORD,
+        pcbProfileName: *mut DWORD,
+    ) -> BOOL;
+    fn WcsGetDefaultColorProfile(
+        scope: DWORD,
+        pDeviceName: LPCWSTR,
+        cptColorProfileType: DWORD,
+        cpstColorProfileSubType: DWORD,
+        dwProfileID: DWORD,
+        cbProfileName: DWORD,
+        pProfileName: LPWSTR,
+    ) -> BOOL;
+    fn WcsSetDefaultColorProfile(
+        scope: DWORD,
+        pDeviceName: LPCWSTR,
+        cptColorProfileType: DWORD,
+        cpstColorProfileSubType: DWORD,
+        dwProfileID: DWORD,
+        pProfileName: LPCWSTR,
+    ) -> BOOL;
+    fn WcsAssociateColorProfileWithDevice(
+        scope: DWORD,
+        pProfileName: LPCWSTR,
+        pDeviceName: LPCWSTR,
+    ) -> BOOL;
+    fn WcsSetUsePerUserProfiles(
+        pDeviceName: LPCWSTR,
+        dwDeviceClass: DWORD,
+        usePerUserProfiles: BOOL,
+    ) -> BOOL;
+    fn InstallColorProfileW(pMachineName: LPCWSTR, pProfileName: LPCWSTR) -> BOOL;
+    fn GetColorDirectoryW(pMachineName: LPCWSTR, pBuffer: LPWSTR, pdwSize: *mut DWORD) -> BOOL;
+}
+
+impl DisplayAdapter {
+    /// The full path of the default ICC profile of the display, or `None` if it has none and the
+    /// system default is used instead.
+    ///
+    /// This is the current user's profile if they have opted into per-user color settings, and
+    /// the system-wide one otherwise.
+    pub fn color_profile(&self) -> Option<String> {
+        let device = self.color_device_key()?;
+
+        let mut size = 0;
+        if unsafe {
+            WcsGetDefaultColorProfileSize(
+                WCS_PROFILE_MANAGEMENT_SCOPE_CURRENT_USER,
+                device.as_ptr(),
+                CPT_ICC,
+                CPST_NONE,
+                0,
+                &mut size,
+            )
+        } == 0
+        {
+            return None;
+        }
+
+        // The size is in bytes
+        let mut name = vec![0u16; size as usize / 2 + 1];
+        if unsafe {
+            WcsGetDefaultColorProfile(
+                WCS_PROFILE_MANAGEMENT_SCOPE_CURRENT_USER,
+                device.as_ptr(),
+                CPT_ICC,
+                CPST_NONE,
+                0,
+                size,
+                name.as_mut_ptr(),
+            )
+        } == 0
+        {
+            return None;
+        }
+
+        let name = string_from_utf16_and_strip_null(&name);
+        if name.is_empty() {
+            return None;
+        }
+
+        // The profile is usually given as just a file name within the color directory.
+        if name.contains('\\') {
+            Some(name)
+        } else {
+            Some(format!("{}\\{}", color_directory()?, name))
+        }
+    }
+
+    /// Makes the ICC profile at `path` the default profile of the display for the current user.
+    ///
+    /// The profile is installed into the system's color directory first if it isn't there
+    /// already.
+    pub fn set_color_profile(&self, path: &str) -> Result<(), ColorProfileError> {
+        validate_profile(Path::new(path))?;
+        let file_name = Path::new(path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or(ColorProfileError::NotFound)?;
+
+        let device = self
+            .color_device_key()
+            .ok_or(ColorProfileError::NoMonitor)?;
+        let installed = match (Path::new(path).parent(), color_directory()) {
+            (Some(parent), Some(directory)) => parent
+                .to_string_lossy()
+                .eq_ignore_ascii_case(directory.trim_end_matches('\\')),
+            _ => false,
+        };
+        let path = to_wide(path);
+        let file_name = to_wide(file_name);
+
+        let ok = unsafe {
+            (installed || InstallColorProfileW(ptr::null(), path.as_ptr()) != 0)
+                && WcsSetUsePerUserProfiles(device.as_ptr(), CLASS_MONITOR, TRUE) != 0
+                && WcsAssociateColorProfileWithDevice(
+                    WCS_PROFILE_MANAGEMENT_SCOPE_CURRENT_USER,
+                    file_name.as_ptr(),
+                    device.as_ptr(),
+                ) != 0
+                && WcsSetDefaultColorProfile(
+                    WCS_PROFILE_MANAGEMENT_SCOPE_CURRENT_USER,
+                    device.as_ptr(),
+                    CPT_ICC,
+                    CPST_NONE,
+                    0,
+                    file_name.as_ptr(),
+                ) != 0
+        };
+
+        if ok {
+            Ok(())
+        } else {
+            log::debug!(
+                "failed to set the color profile: {}",
+                crate::last_os_error()
+            );
+            Err(ColorProfileError::Failed)
+        }
+    }
+
+    /// The WCS functions identify displays by the registry key of the monitor.
+    fn color_device_key(&self) -> Option<Vec<u16>> {
+        let monitors = self.monitors();
+        let monitor = monitors
+            .iter()
+            .find(|monitor| monitor.active())
+            .or_else(|| monitors.iter().next())?;
+
+        Some(to_wide(&monitor.key))
+    }
+}
+
+fn color_directory() -> Option<String> {
+    let mut size = 0;
+    unsafe { GetColorDirectoryW(ptr::null(), ptr::null_mut(), &mut size) };
+    if size == 0 {
+        return None;
+    }
+
+    // The size is in bytes
+    let mut directory = vec![0u16; size as usize / 2 + 1];
+    if unsafe { GetColorDirectoryW(ptr::null(), directory.as_mut_ptr(), &mut size) } == 0 {
+        return None;
+    }
+
+    Some(string_from_utf16_and_strip_null(&directory))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorProfileError {
+    /// The profile file doesn't exist or couldn't be read.
+    NotFound,
+    /// The file isn't an ICC profile.
+    NotAProfile,
+    /// No monitor is connected to the adapter.
+    NoMonitor,
+    Failed,
+}
+
+impl std::fmt::Display for ColorProfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::NotFound => "the color profile doesn't exist or couldn't be read",
+            Self::NotAProfile => "the file is not an ICC profile",
+            Self::NoMonitor => "no monitor is connected to the adapter",
+            Self::Failed => "failed to set the color profile",
+        })
+    }
+}
+
+impl std::error::Error for ColorProfileError {}
+
+/// Checks for the `acsp` signature in the ICC profile header.
+fn validate_profile(path: &Path) -> Result<(), ColorProfileError> {
+    let mut header = [0; 128];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .map_err(|_| ColorProfileError::NotFound)?;
+
+    if &header[36..40] == b"acsp" {
+        Ok(())
+    } else {
+        Err(ColorProfileError::NotAProfile)
+    }
+}
@@ -0,0 +1,144 @@
+//! The kernel-mode thunks of the display driver interface, for the few things GDI can't do.
+//!
+//! https://docs.microsoft.com/en-us/windows-hardware/drivers/ddi/d3dkmthk/
+
+use std::mem;
+
+use winapi::shared::{
+    minwindef::UINT,
+    ntdef::{LUID, NTSTATUS, WCHAR},
+};
+
+use crate::DisplayAdapter;
+
+// These aren't exposed by winapi 0.3
+#[allow(non_camel_case_types)]
+type D3DKMT_HANDLE = UINT;
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct D3DKMT_OPENADAPTERFROMGDIDISPLAYNAME {
+    DeviceName: [WCHAR; 32],
+    hAdapter: D3DKMT_HANDLE,
+    AdapterLuid: LUID,
+    VidPnSourceId: UINT,
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct D3DKMT_CLOSEADAPTER {
+    hAdapter: D3DKMT_HANDLE,
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct D3DKMT_WAITFORVERTICALBLANKEVENT {
+    hAdapter: D3DKMT_HANDLE,
+    hDevice: D3DKMT_HANDLE,
+    VidPnSourceId: UINT,
+}
+
+#[link(name = "gdi32")]
+extern "system" {
+    fn D3DKMTOpenAdapterFromGdiDisplayName(
+        pData: *mut D3DKMT_OPENADAPTERFROMGDIDISPLAYNAME,
+    ) -> NTSTATUS;
+    fn D3DKMTCloseAdapter(pData: *const D3DKMT_CLOSEADAPTER) -> NTSTATUS;
+    fn D3DKMTWaitForVerticalBlankEvent(pData: *const D3DKMT_WAITFORVERTICALBLANKEVENT) -> NTSTATUS;
+}
+
+const STATUS_SUCCESS: NTSTATUS = 0;
+
+impl DisplayAdapter {
+    /// Blocks until the next vertical blank of the display.
+    ///
+    /// https://docs.microsoft.com/en-us/windows-hardware/drivers/ddi/d3dkmthk/nf-d3dkmthk-d3dkmtwaitforverticalblankevent
+    pub fn wait_for_vblank(&self) -> Result<(), GpuError> {
+        let adapter = KmtAdapter::open(self)?;
+
+        let wait = D3DKMT_WAITFORVERTICALBLANKEVENT {
+            hAdapter: adapter.raw.hAdapter,
+            hDevice: 0,
+            VidPnSourceId: adapter.raw.VidPnSourceId,
+        };
+        let status = unsafe { D3DKMTWaitForVerticalBlankEvent(&wait) };
+        if status == STATUS_SUCCESS {
+            Ok(())
+        } else {
+            log::debug!("D3DKMTWaitForVerticalBlankEvent returned {:#x}", status);
+            Err(GpuError::Failed)
+        }
+    }
+
+    /// The LUID of the GPU driving the display and the display's video present source on it.
+    ///
+    /// This is what DXGI and the CCD API use to identify the display, rather than the GDI name.
+    pub fn gpu_identity(&self) -> Option<GpuIdentity> {
+        let adapter = KmtAdapter::open(self).ok()?;
+
+        Some(GpuIdentity {
+            luid: (
+                adapter.raw.AdapterLuid.LowPart,
+                adapter.raw.AdapterLuid.HighPart,
+            ),
+            source_id: adapter.raw.VidPnSourceId,
+        })
+    }
+}
+
+/// Identifies a display by the GPU driving it, as returned by `DisplayAdapter::gpu_identity`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GpuIdentity {
+    /// The adapter LUID, as its low and high parts.
+    pub luid: (u32, i32),
+    /// The VidPN source ID.
+    pub source_id: u32,
+}
+
+/// A D3DKMT adapter handle, which is closed on drop.
+struct KmtAdapter {
+    raw: D3DKMT_OPENADAPTERFROMGDIDISPLAYNAME,
+}
+
+impl KmtAdapter {
+    fn open(adapter: &DisplayAdapter) -> Result<Self, GpuError> {
+        let mut raw: D3DKMT_OPENADAPTERFROMGDIDISPLAYNAME = unsafe { mem::zeroed() };
+        raw.DeviceName = adapter.raw.DeviceName;
+
+        let status = unsafe { D3DKMTOpenAdapterFromGdiDisplayName(&mut raw) };
+        if status == STATUS_SUCCESS {
+            Ok(Self { raw })
+        } else {
+            log::debug!("D3DKMTOpenAdapterFromGdiDisplayName returned {:#x}", status);
+            Err(GpuError::NotAGpuOutput)
+        }
+    }
+}
+
+impl Drop for KmtAdapter {
+    fn drop(&mut self) {
+        let close = D3DKMT_CLOSEADAPTER {
+            hAdapter: self.raw.hAdapter,
+        };
+        unsafe { D3DKMTCloseAdapter(&close) };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GpuError {
+    /// The adapter isn't backed by a GPU output, e.g. because it's inactive or a mirroring
+    /// driver.
+    NotAGpuOutput,
+    Failed,
+}
+
+impl std::fmt::Display for GpuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::NotAGpuOutput => "the adapter is not a GPU output",
+            Self::Failed => "the graphics driver call failed",
+        })
+    }
+}
+
+impl std::error::Error for GpuError {}
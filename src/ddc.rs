@@ -0,0 +1,633 @@
+//! Monitor control through DDC/CI.
+//!
+//! https://docs.microsoft.com/en-us/windows/win32/monitor/monitor-configuration
+
+use std::{
+    mem, ptr, slice, thread,
+    time::{Duration, Instant},
+};
+
+use winapi::{
+    shared::{
+        windef::HMONITOR,
+        winerror::{
+            ERROR_GRAPHICS_DDCCI_VCP_NOT_SUPPORTED, ERROR_GRAPHICS_I2C_DEVICE_DOES_NOT_EXIST,
+            ERROR_GRAPHICS_I2C_NOT_SUPPORTED, ERROR_GRAPHICS_MCA_UNSUPPORTED_MCCS_VERSION,
+            ERROR_INVALID_PARAMETER,
+        },
+    },
+    um::{
+        errhandlingapi::GetLastError,
+        lowlevelmonitorconfigurationapi::{
+            CapabilitiesRequestAndCapabilitiesReply, GetCapabilitiesStringLength,
+            GetVCPFeatureAndVCPFeatureReply, SetVCPFeature,
+        },
+        physicalmonitorenumerationapi::{
+            DestroyPhysicalMonitor, GetNumberOfPhysicalMonitorsFromHMONITOR,
+            GetPhysicalMonitorsFromHMONITOR, PHYSICAL_MONITOR,
+        },
+        winnt::{HANDLE, HRESULT},
+    },
+};
+
+use crate::{string_from_utf16_and_strip_null, DisplayAdapter};
+
+// VCP codes from the VESA Monitor Control Command Set (MCCS).
+const VCP_RESTORE_FACTORY_DEFAULTS: u8 = 0x04;
+const VCP_RESTORE_COLOR_DEFAULTS: u8 = 0x08;
+const VCP_AUDIO_SPEAKER_VOLUME: u8 = 0x62;
+
+const DEFAULT_RETRIES: u32 = 2;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+// The DDC/CI spec requires at least 50 ms between a reply and the next request.
+const RETRY_DELAY: Duration = Duration::from_millis(50);
+
+impl DisplayAdapter {
+    /// The physical monitors this adapter's output is shown on.
+    ///
+    /// This is usually a single monitor, but can be more when the output is duplicated.
+    pub fn physical_monitors(&self) -> Result<PhysicalMonitors, DdcError> {
+        let hmonitor = self.hmonitor().ok_or(DdcError::NoPhysicalMonitors)?;
+        PhysicalMonitors::from_hmonitor(hmonitor)
+    }
+}
+
+/// The physical monitors associated with an `HMONITOR`.
+pub struct PhysicalMonitors {
+    monitors: Vec<PhysicalMonitor>,
+}
+
+impl PhysicalMonitors {
+    fn from_hmonitor(hmonitor: HMONITOR) -> Result<Self, DdcError> {
+        let mut count = 0;
+        if unsafe { GetNumberOfPhysicalMonitorsFromHMONITOR(hmonitor, &mut count) } == 0 {
+            return Err(DdcError::last());
+        }
+        if count == 0 {
+            return Err(DdcError::NoPhysicalMonitors);
+        }
+
+        let mut raw: Vec<PHYSICAL_MONITOR> = vec![unsafe { mem::zeroed() }; count as usize];
+        if unsafe { GetPhysicalMonitorsFromHMONITOR(hmonitor, count, raw.as_mut_ptr()) } == 0 {
+            return Err(DdcError::last());
+        }
+
+        // Each handle is owned by its `PhysicalMonitor` from here on, so the array itself doesn't
+        // need to go through `DestroyPhysicalMonitors`.
+        let monitors = raw
+            .iter()
+            .map(|monitor| {
+                // The struct is packed, so the fields have to be copied out before use.
+                let handle = monitor.hPhysicalMonitor;
+                let description = monitor.szPhysicalMonitorDescription;
+                PhysicalMonitor {
+                    handle,
+                    description: string_from_utf16_and_strip_null(&description),
+                    retries: DEFAULT_RETRIES,
+                    timeout: DEFAULT_TIMEOUT,
+                }
+            })
+            .collect();
+
+        Ok(Self { monitors })
+    }
+
+    pub fn len(&self) -> usize {
+        self.monitors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.monitors.is_empty()
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, PhysicalMonitor> {
+        self.monitors.iter()
+    }
+}
+
+impl IntoIterator for PhysicalMonitors {
+    type Item = PhysicalMonitor;
+    type IntoIter = std::vec::IntoIter<PhysicalMonitor>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.monitors.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a PhysicalMonitors {
+    type Item = &'a PhysicalMonitor;
+    type IntoIter = slice::Iter<'a, PhysicalMonitor>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.monitors.iter()
+    }
+}
+
+/// A handle to a physical monitor. The handle is destroyed when this is dropped.
+///
+/// This is `Send` but not `Sync`: the handle can be used from any thread, but concurrent VCP
+/// transactions on the same monitor would interleave on the wire.
+#[derive(Debug)]
+pub struct PhysicalMonitor {
+    handle: HANDLE,
+    pub description: String,
+    retries: u32,
+    timeout: Duration,
+}
+
+impl PhysicalMonitor {
+    /// Sets how many times a failed VCP transaction is retried before giving up.
+    ///
+    /// DDC/CI is unreliable enough that an occasional failure is expected, so this defaults to 2.
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets how long a VCP transaction may keep retrying, including the delays between attempts.
+    /// No retry is started which would end after the timeout.
+    ///
+    /// Defaults to 1 second.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The raw DDC/CI capabilities string, like `(prot(monitor)type(lcd)...vcp(10 12 60(...)))`.
+    pub fn capabilities(&self) -> Result<String, DdcError> {
+        let mut len = 0;
+        if unsafe { GetCapabilitiesStringLength(self.handle, &mut len) } == 0 {
+            return Err(DdcError::last());
+        }
+
+        let mut buffer = vec![0u8; len as usize];
+        if unsafe {
+            CapabilitiesRequestAndCapabilitiesReply(self.handle, buffer.as_mut_ptr() as _, len)
+        } == 0
+        {
+            return Err(DdcError::last());
+        }
+
+        // The string is ASCII, but don't trust the monitor to get that right.
+        let end = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+        Ok(String::from_utf8_lossy(&buffer[..end]).into_owned())
+    }
+
+    /// Resets all settings to their factory defaults (VCP code `0x04`).
+    pub fn restore_factory_defaults(&self) -> Result<(), DdcError> {
+        // The reset codes are write-only and any non-zero value triggers them.
+        self.set_vcp(VCP_RESTORE_FACTORY_DEFAULTS, 1)
+    }
+
+    /// Resets the color settings to their factory defaults (VCP code `0x08`).
+    pub fn restore_color_defaults(&self) -> Result<(), DdcError> {
+        self.set_vcp(VCP_RESTORE_COLOR_DEFAULTS, 1)
+    }
+
+    /// The speaker volume (VCP code `0x62`).
+    pub fn volume(&self) -> Result<VcpValue, DdcError> {
+        self.get_vcp(VCP_AUDIO_SPEAKER_VOLUME)
+    }
+
+    /// Sets the speaker volume to a value between 0 and `volume().maximum`.
+    pub fn set_volume(&self, value: u32) -> Result<(), DdcError> {
+        self.set_vcp(VCP_AUDIO_SPEAKER_VOLUME, value)
+    }
+
+    fn get_vcp(&self, code: u8) -> Result<VcpValue, DdcError> {
+        retry(self.retries, RETRY_DELAY, self.timeout, || {
+            let (mut current, mut maximum) = (0, 0);
+            let ret = unsafe {
+                GetVCPFeatureAndVCPFeatureReply(
+                    self.handle,
+                    code,
+                    ptr::null_mut(),
+                    &mut current,
+                    &mut maximum,
+                )
+            };
+
+            // Replies with a bad checksum or a current value above the maximum are rejected by
+            // Windows, so a successful reply can be trusted.
+            if ret != 0 {
+                Ok(VcpValue { current, maximum })
+            } else {
+                Err(DdcError::last())
+            }
+        })
+    }
+
+    fn set_vcp(&self, code: u8, value: u32) -> Result<(), DdcError> {
+        retry(self.retries, RETRY_DELAY, self.timeout, || {
+            if unsafe { SetVCPFeature(self.handle, code, value) } != 0 {
+                Ok(())
+            } else {
+                Err(DdcError::last())
+            }
+        })
+    }
+}
+
+/// Runs `transaction` until it succeeds, fails with something other than
+/// `DdcError::CommunicationFailed`, runs out of retries, or another attempt would exceed
+/// `timeout`. Attempts are `delay` apart.
+fn retry<T>(
+    retries: u32,
+    delay: Duration,
+    timeout: Duration,
+    mut transaction: impl FnMut() -> Result<T, DdcError>,
+) -> Result<T, DdcError> {
+    let start = Instant::now();
+    let mut attempts = 0;
+    loop {
+        match transaction() {
+            Err(DdcError::CommunicationFailed)
+                if attempts < retries && start.elapsed() + delay <= timeout =>
+            {
+                attempts += 1;
+                log::debug!(
+                    "DDC/CI transaction failed, retrying ({}/{})",
+                    attempts,
+                    retries
+                );
+                // The monitor needs some time to recover before it accepts another message.
+                thread::sleep(delay);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// The value of a continuous VCP feature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VcpValue {
+    pub current: u32,
+    pub maximum: u32,
+}
+
+// The handle isn't tied to the thread which created it.
+unsafe impl Send for PhysicalMonitor {}
+
+impl Drop for PhysicalMonitor {
+    fn drop(&mut self) {
+        unsafe { DestroyPhysicalMonitor(self.handle) };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DdcError {
+    /// The monitor doesn't support DDC/CI or the requested VCP feature.
+    Unsupported,
+    /// The monitor didn't respond, or responded with garbage.
+    CommunicationFailed,
+    /// The value is out of range for the feature.
+    InvalidValue,
+    /// There are no physical monitors to talk to, for example because the display is inactive.
+    NoPhysicalMonitors,
+}
+
+impl DdcError {
+    /// Maps the thread's last Win32 error after a failed DDC/CI call.
+    fn last() -> Self {
+        let code = unsafe { GetLastError() };
+        log::debug!("DDC/CI call failed: {}", crate::last_os_error());
+
+        match code as HRESULT {
+            ERROR_GRAPHICS_I2C_NOT_SUPPORTED
+            | ERROR_GRAPHICS_DDCCI_VCP_NOT_SUPPORTED
+            | ERROR_GRAPHICS_MCA_UNSUPPORTED_MCCS_VERSION => Self::Unsupported,
+            ERROR_GRAPHICS_I2C_DEVICE_DOES_NOT_EXIST => Self::NoPhysicalMonitors,
+            n if n == ERROR_INVALID_PARAMETER as HRESULT => Self::InvalidValue,
+            _ => Self::CommunicationFailed,
+        }
+    }
+}
+
+impl std::fmt::Display for DdcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Unsupported => "the monitor doesn't support this DDC/CI feature",
+            Self::CommunicationFailed => "DDC/CI communication with the monitor failed",
+            Self::InvalidValue => "invalid value for the VCP feature",
+            Self::NoPhysicalMonitors => "no physical monitors found",
+        })
+    }
+}
+
+impl std::error::Error for DdcError {}
+
+/// The parsed form of a DDC/CI capabilities string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    pub vcp: Vec<VcpFeature>,
+}
+
+impl Capabilities {
+    /// Parses the string returned by `PhysicalMonitor::capabilities`.
+    ///
+    /// Returns `None` if the string is malformed.
+    pub fn parse(capabilities: &str) -> Option<Self> {
+        // Some monitors leave out the outermost parentheses.
+        let capabilities = capabilities.trim();
+        let inner = match capabilities.strip_prefix('(') {
+            Some(rest) => rest.strip_suffix(')')?,
+            None => capabilities,
+        };
+
+        let mut vcp = Vec::new();
+        for (key, value) in sections(inner)? {
+            if key == "vcp" {
+                vcp = parse_vcp(value)?;
+            }
+        }
+
+        Some(Self { vcp })
+    }
+}
+
+/// A VCP code supported by a monitor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VcpFeature {
+    pub code: u8,
+    /// The values the feature accepts, if the monitor lists them. This is typically only the case
+    /// for non-continuous features like the input source.
+    pub values: Option<Vec<u8>>,
+}
+
+/// Splits `key(value)key(value)...` into its key-value pairs.
+fn sections(s: &str) -> Option<Vec<(&str, &str)>> {
+    let mut sections = Vec::new();
+    let mut rest = s.trim_start();
+
+    while !rest.is_empty() {
+        let open = rest.find('(')?;
+        let close = open + matching_paren(&rest[open..])?;
+        let key = rest[..open].trim();
+        // A stray closing parenthesis
+        if key.contains(')') {
+            return None;
+        }
+        sections.push((key, &rest[open + 1..close]));
+        rest = rest[close + 1..].trim_start();
+    }
+
+    Some(sections)
+}
+
+/// The index of the parenthesis closing the one `s` starts with.
+fn matching_paren(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Parses the contents of the `vcp(...)` section, like `10 12 60(01 03 0F)`.
+fn parse_vcp(s: &str) -> Option<Vec<VcpFeature>> {
+    let mut features: Vec<VcpFeature> = Vec::new();
+    let mut rest = s.trim_start();
+
+    while !rest.is_empty() {
+        if rest.starts_with('(') {
+            let close = matching_paren(rest)?;
+            let inner = &rest[1..close];
+            if inner.contains('(') {
+                return None;
+            }
+
+            let feature = features.last_mut()?;
+            if feature.values.is_some() {
+                return None;
+            }
+            feature.values = Some(hex_bytes(inner)?);
+            rest = &rest[close + 1..];
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || c == '(')
+                .unwrap_or(rest.len());
+            features.extend(
+                hex_bytes(&rest[..end])?
+                    .into_iter()
+                    .map(|code| VcpFeature { code, values: None }),
+            );
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+
+    Some(features)
+}
+
+/// Parses whitespace-separated hex bytes. Some monitors don't put spaces between the codes, so
+/// longer runs of digits are split into pairs.
+fn hex_bytes(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+
+    for token in s.split_whitespace() {
+        if !token.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        if token.len() <= 2 {
+            bytes.push(u8::from_str_radix(token, 16).ok()?);
+        } else if token.len() % 2 == 0 {
+            for pair in token.as_bytes().chunks(2) {
+                bytes.push(u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?);
+            }
+        } else {
+            return None;
+        }
+    }
+
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+
+    /// A transaction which fails with `error` the first `failures` times.
+    fn flaky(
+        failures: u32,
+        error: DdcError,
+    ) -> (impl FnMut() -> Result<u32, DdcError>, Rc<Cell<u32>>) {
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        let transaction = move || {
+            counter.set(counter.get() + 1);
+            if counter.get() <= failures {
+                Err(error)
+            } else {
+                Ok(42)
+            }
+        };
+
+        (transaction, calls)
+    }
+
+    #[test]
+    fn retry_returns_value_after_failures() {
+        let (transaction, calls) = flaky(2, DdcError::CommunicationFailed);
+
+        let result = retry(
+            2,
+            Duration::from_millis(0),
+            Duration::from_secs(1),
+            transaction,
+        );
+
+        assert_eq!(result, Ok(42));
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn retry_gives_up_after_retries() {
+        let (transaction, calls) = flaky(3, DdcError::CommunicationFailed);
+
+        let result = retry(
+            2,
+            Duration::from_millis(0),
+            Duration::from_secs(1),
+            transaction,
+        );
+
+        assert_eq!(result, Err(DdcError::CommunicationFailed));
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn retry_only_retries_communication_failures() {
+        for &error in &[
+            DdcError::Unsupported,
+            DdcError::InvalidValue,
+            DdcError::NoPhysicalMonitors,
+        ] {
+            let (transaction, calls) = flaky(1, error);
+
+            let result = retry(
+                2,
+                Duration::from_millis(0),
+                Duration::from_secs(1),
+                transaction,
+            );
+
+            assert_eq!(result, Err(error));
+            assert_eq!(calls.get(), 1);
+        }
+    }
+
+    #[test]
+    fn retry_stops_at_timeout() {
+        let (transaction, calls) = flaky(1, DdcError::CommunicationFailed);
+
+        let result = retry(
+            2,
+            Duration::from_millis(20),
+            Duration::from_millis(10),
+            transaction,
+        );
+
+        assert_eq!(result, Err(DdcError::CommunicationFailed));
+        assert_eq!(calls.get(), 1);
+    }
+
+    fn feature(code: u8, values: Option<&[u8]>) -> VcpFeature {
+        VcpFeature {
+            code,
+            values: values.map(<[u8]>::to_vec),
+        }
+    }
+
+    #[test]
+    fn parse_real_world_capabilities() {
+        let capabilities = Capabilities::parse(
+            "(prot(monitor)type(lcd)model(U2718Q)cmds(01 02 03 07 0C E3 F3)\
+             vcp(02 04 10 12 14(05 08 0B) 60( 11 12 0F) DF)mccs_ver(2.1))",
+        )
+        .unwrap();
+
+        assert_eq!(
+            capabilities.vcp,
+            vec![
+                feature(0x02, None),
+                feature(0x04, None),
+                feature(0x10, None),
+                feature(0x12, None),
+                feature(0x14, Some(&[0x05, 0x08, 0x0b])),
+                feature(0x60, Some(&[0x11, 0x12, 0x0f])),
+                feature(0xdf, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_without_outer_parentheses() {
+        let capabilities = Capabilities::parse("prot(monitor)vcp(10 12)").unwrap();
+
+        assert_eq!(
+            capabilities.vcp,
+            vec![feature(0x10, None), feature(0x12, None)]
+        );
+    }
+
+    #[test]
+    fn parse_run_together_codes() {
+        let capabilities = Capabilities::parse("(vcp(101214(0102)))").unwrap();
+
+        assert_eq!(
+            capabilities.vcp,
+            vec![
+                feature(0x10, None),
+                feature(0x12, None),
+                feature(0x14, Some(&[0x01, 0x02])),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_without_vcp_section() {
+        assert_eq!(
+            Capabilities::parse("(prot(monitor)type(lcd))"),
+            Some(Capabilities { vcp: Vec::new() })
+        );
+    }
+
+    #[test]
+    fn reject_unbalanced_parentheses() {
+        assert_eq!(Capabilities::parse("(prot(monitor)vcp(10 12)"), None);
+        assert_eq!(Capabilities::parse("prot(monitor)vcp(10 12"), None);
+        assert_eq!(Capabilities::parse("prot(monitor))vcp(10 12)"), None);
+        assert_eq!(Capabilities::parse("(vcp(10 12) 14))"), None);
+    }
+
+    #[test]
+    fn reject_values_without_code() {
+        assert_eq!(Capabilities::parse("(vcp((01 02) 10))"), None);
+        assert_eq!(Capabilities::parse("(vcp(10(01) (02)))"), None);
+    }
+
+    #[test]
+    fn reject_nested_values() {
+        assert_eq!(Capabilities::parse("(vcp(14((05 08))))"), None);
+    }
+
+    #[test]
+    fn reject_non_hex_tokens() {
+        assert_eq!(Capabilities::parse("(vcp(10 XY))"), None);
+        assert_eq!(Capabilities::parse("(vcp(123))"), None);
+        assert_eq!(Capabilities::parse("(vcp(14(05 0G)))"), None);
+    }
+}
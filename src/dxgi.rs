@@ -0,0 +1,81 @@
+//! Correlating adapters with DXGI outputs.
+//!
+//! https://docs.microsoft.com/en-us/windows/win32/api/dxgi/
+
+use std::{ops::Deref, ptr};
+
+use winapi::{
+    shared::{
+        dxgi::{CreateDXGIFactory1, IDXGIAdapter1, IDXGIFactory1, IDXGIOutput, DXGI_OUTPUT_DESC},
+        winerror::S_OK,
+    },
+    um::unknwnbase::IUnknown,
+    Interface,
+};
+
+use crate::{string_from_utf16_and_strip_null, DisplayAdapter};
+
+impl DisplayAdapter {
+    /// The index of the DXGI adapter and of the output on it which show this display, as passed
+    /// to `IDXGIFactory1::EnumAdapters1` and `IDXGIAdapter::EnumOutputs`.
+    ///
+    /// Returns `None` if no output matches, e.g. because the adapter isn't active.
+    pub fn dxgi_output_index(&self) -> Option<(u32, u32)> {
+        let factory = unsafe {
+            let mut factory = ptr::null_mut();
+            if CreateDXGIFactory1(&IDXGIFactory1::uuidof(), &mut factory) != S_OK {
+                return None;
+            }
+            Com(factory as *mut IDXGIFactory1)
+        };
+
+        for adapter_index in 0.. {
+            let adapter = unsafe {
+                let mut adapter = ptr::null_mut();
+                if factory.EnumAdapters1(adapter_index, &mut adapter) != S_OK {
+                    break;
+                }
+                Com::<IDXGIAdapter1>(adapter)
+            };
+
+            for output_index in 0.. {
+                let output = unsafe {
+                    let mut output = ptr::null_mut();
+                    if adapter.EnumOutputs(output_index, &mut output) != S_OK {
+                        break;
+                    }
+                    Com::<IDXGIOutput>(output)
+                };
+
+                let mut desc: DXGI_OUTPUT_DESC = unsafe { std::mem::zeroed() };
+                if unsafe { output.GetDesc(&mut desc) } != S_OK {
+                    continue;
+                }
+
+                // The output's device name is the GDI name of the adapter, e.g. `\\.\DISPLAY1`.
+                if string_from_utf16_and_strip_null(&desc.DeviceName) == self.name {
+                    return Some((adapter_index, output_index));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// An owned COM pointer, which is released on drop.
+struct Com<T: Interface>(*mut T);
+
+impl<T: Interface> Deref for Com<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.0 }
+    }
+}
+
+impl<T: Interface> Drop for Com<T> {
+    fn drop(&mut self) {
+        unsafe { (*(self.0 as *mut IUnknown)).Release() };
+    }
+}
@@ -0,0 +1,180 @@
+//! Reading the EDID of monitors from the registry.
+//!
+//! https://docs.microsoft.com/en-us/windows-hardware/drivers/display/monitor-inf-file-sections
+
+use std::ptr;
+
+use winapi::{
+    shared::winerror::ERROR_SUCCESS,
+    um::winreg::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_BINARY},
+};
+
+use crate::{best, to_wide, DisplayAdapter, Monitor, Persistence, SetDisplaySettingsError};
+
+impl DisplayAdapter {
+    /// The resolution the monitor is built for, in its unrotated orientation.
+    ///
+    /// This is the preferred timing from the monitor's EDID. If that can't be read, the largest
+    /// supported mode is used instead.
+    pub fn native_resolution(&self) -> Option<(u32, u32)> {
+        let monitors = self.monitors();
+        let from_edid = monitors
+            .iter()
+            .find(|monitor| monitor.active())
+            .or_else(|| monitors.iter().next())
+            .and_then(Monitor::edid)
+            .and_then(|edid| preferred_resolution(&edid));
+
+        from_edid.or_else(|| best(self.modes()).map(|mode| (mode.width, mode.height)))
+    }
+
+    /// Switches to the native resolution at the highest refresh rate supported with 32 bits per
+    /// pixel. Does nothing if that's already the current mode.
+    pub fn set_native(&self, persistence: Persistence) -> Result<(), SetDisplaySettingsError> {
+        let current = self
+            .current_mode()
+            .ok_or(SetDisplaySettingsError::BadParam)?;
+        let (mut width, mut height) = self
+            .native_resolution()
+            .ok_or(SetDisplaySettingsError::BadMode)?;
+        // Keep the display in portrait if it's rotated.
+        if (width >= height) != (current.width >= current.height) {
+            std::mem::swap(&mut width, &mut height);
+        }
+
+        let frequency = self
+            .modes()
+            .filter(|mode| mode.width == width && mode.height == height && mode.bits_per_pel == 32)
+            .map(|mode| mode.frequency)
+            .max()
+            .ok_or(SetDisplaySettingsError::BadMode)?;
+
+        self.change()
+            .resolution(width, height)
+            .frequency(frequency)
+            .bits_per_pel(32)
+            .persistence(persistence)
+            .apply()?
+            .into_result()
+    }
+}
+
+impl Monitor {
+    /// The raw EDID which Windows has stored for the monitor.
+    fn edid(&self) -> Option<Vec<u8>> {
+        // `\\?\DISPLAY#DEL4058#5&2a1b...&0&UID4352#{GUID}` is the interface path of the device
+        // instance `DISPLAY\DEL4058\5&2a1b...&0&UID4352`.
+        let path = self.interface_path()?;
+        let instance = path.strip_prefix(r"\\?\")?;
+        let instance = &instance[..instance.rfind('#')?];
+        let key = to_wide(&format!(
+            r"SYSTEM\CurrentControlSet\Enum\{}\Device Parameters",
+            instance.replace('#', r"\")
+        ));
+        let value = to_wide("EDID");
+
+        let mut size = 0;
+        let ret = unsafe {
+            RegGetValueW(
+                HKEY_LOCAL_MACHINE,
+                key.as_ptr(),
+                value.as_ptr(),
+                RRF_RT_REG_BINARY,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                &mut size,
+            )
+        };
+        if ret != ERROR_SUCCESS as i32 {
+            log::debug!("no EDID for {}: RegGetValueW returned {}", self.name, ret);
+            return None;
+        }
+
+        let mut edid = vec![0u8; size as usize];
+        let ret = unsafe {
+            RegGetValueW(
+                HKEY_LOCAL_MACHINE,
+                key.as_ptr(),
+                value.as_ptr(),
+                RRF_RT_REG_BINARY,
+                ptr::null_mut(),
+                edid.as_mut_ptr().cast(),
+                &mut size,
+            )
+        };
+        if ret != ERROR_SUCCESS as i32 {
+            return None;
+        }
+
+        edid.truncate(size as usize);
+        Some(edid)
+    }
+}
+
+/// The active resolution of the first detailed timing descriptor, which is the preferred timing.
+fn preferred_resolution(edid: &[u8]) -> Option<(u32, u32)> {
+    if edid.get(..8)? != [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00] {
+        return None;
+    }
+
+    let descriptor = edid.get(54..72)?;
+    // A zero pixel clock marks a display descriptor instead of a timing.
+    if descriptor[0] == 0 && descriptor[1] == 0 {
+        return None;
+    }
+
+    let width = descriptor[2] as u32 | (descriptor[4] as u32 & 0xf0) << 4;
+    let height = descriptor[5] as u32 | (descriptor[7] as u32 & 0xf0) << 4;
+    if width == 0 || height == 0 {
+        None
+    } else {
+        Some((width, height))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The base block of a 27" 2560x1440 monitor.
+    #[rustfmt::skip]
+    const EDID: [u8; 128] = [
+        0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x10, 0xac, 0xa0, 0xd0, 0x4c, 0x33, 0x35, 0x30,
+        0x01, 0x19, 0x01, 0x04, 0xa5, 0x3c, 0x22, 0x78, 0x3a, 0xee, 0x91, 0xa3, 0x54, 0x4c, 0x99, 0x26,
+        0x0f, 0x50, 0x54, 0xa5, 0x4b, 0x00, 0x71, 0x4f, 0x81, 0x80, 0xa9, 0xc0, 0xd1, 0xc0, 0x01, 0x01,
+        0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x56, 0x5e, 0x00, 0xa0, 0xa0, 0xa0, 0x29, 0x50, 0x30, 0x20,
+        0x35, 0x00, 0x55, 0x50, 0x21, 0x00, 0x00, 0x1a, 0x00, 0x00, 0x00, 0xff, 0x00, 0x47, 0x48, 0x38,
+        0x35, 0x44, 0x35, 0x41, 0x55, 0x30, 0x53, 0x35, 0x4c, 0x0a, 0x00, 0x00, 0x00, 0xfc, 0x00, 0x44,
+        0x45, 0x4c, 0x4c, 0x20, 0x55, 0x32, 0x37, 0x31, 0x35, 0x48, 0x0a, 0x20, 0x00, 0x00, 0x00, 0xfd,
+        0x00, 0x38, 0x4c, 0x1e, 0x58, 0x19, 0x00, 0x0a, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00, 0x04,
+    ];
+
+    #[test]
+    fn preferred_resolution_from_timing() {
+        assert_eq!(preferred_resolution(&EDID), Some((2560, 1440)));
+    }
+
+    #[test]
+    fn preferred_resolution_bad_header() {
+        let mut edid = EDID;
+        edid[0] = 0xff;
+
+        assert_eq!(preferred_resolution(&edid), None);
+    }
+
+    #[test]
+    fn preferred_resolution_display_descriptor() {
+        // The first descriptor replaced by the monitor name descriptor.
+        let mut edid = EDID;
+        edid.copy_within(90..108, 54);
+
+        assert_eq!(preferred_resolution(&edid), None);
+    }
+
+    #[test]
+    fn preferred_resolution_truncated() {
+        assert_eq!(preferred_resolution(&EDID[..60]), None);
+        assert_eq!(preferred_resolution(&EDID[..4]), None);
+        assert_eq!(preferred_resolution(&[]), None);
+    }
+}
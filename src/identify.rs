@@ -0,0 +1,172 @@
+use std::{mem, ptr, time::Duration};
+
+use winapi::{
+    shared::{
+        minwindef::{LPARAM, LRESULT, UINT, WPARAM},
+        windef::{HWND, RECT},
+    },
+    um::{
+        libloaderapi::GetModuleHandleW,
+        wingdi::{
+            CreateFontW, DeleteObject, GetStockObject, SelectObject, SetBkMode, SetTextColor,
+            BLACK_BRUSH, DEFAULT_CHARSET, FW_BOLD, RGB, TRANSPARENT,
+        },
+        winuser::{
+            BeginPaint, CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW,
+            DrawTextW, EndPaint, FillRect, GetClientRect, GetMessageW, GetWindowLongPtrW,
+            KillTimer, PostQuitMessage, RegisterClassExW, SetLayeredWindowAttributes, SetTimer,
+            SetWindowLongPtrW, ShowWindow, TranslateMessage, DT_CENTER, DT_SINGLELINE, DT_VCENTER,
+            GWLP_USERDATA, LWA_ALPHA, MSG, PAINTSTRUCT, SW_SHOWNOACTIVATE, WM_PAINT, WM_TIMER,
+            WNDCLASSEXW, WS_EX_LAYERED, WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW, WS_EX_TOPMOST,
+            WS_EX_TRANSPARENT, WS_POPUP,
+        },
+    },
+};
+
+use crate::{to_wide, DisplayAdapters};
+
+const CLASS_NAME: &str = "monman_identify";
+const TIMER_ID: usize = 1;
+
+impl DisplayAdapters {
+    /// Briefly shows a large number in the corner of each active display, like the "Identify"
+    /// button in the Windows display settings.
+    ///
+    /// The number is the adapter's index plus one. This blocks the calling thread for `duration`.
+    pub fn identify(&self, duration: Duration) {
+        let class_name = to_wide(CLASS_NAME);
+        let hinstance = unsafe { GetModuleHandleW(ptr::null()) };
+
+        let mut class: WNDCLASSEXW = unsafe { mem::zeroed() };
+        class.cbSize = mem::size_of::<WNDCLASSEXW>() as u32;
+        class.lpfnWndProc = Some(window_proc);
+        class.hInstance = hinstance;
+        class.lpszClassName = class_name.as_ptr();
+
+        // This fails if the class has already been registered by a previous call, which is fine.
+        unsafe { RegisterClassExW(&class) };
+
+        let mut windows = Vec::new();
+        for (i, adapter) in self.iter().enumerate() {
+            if !adapter.state.active() {
+                continue;
+            }
+
+            let info = adapter.info();
+            let (position, width, height) = match (info.position, info.pels_width, info.pels_height)
+            {
+                (Some(position), Some(width), Some(height)) => (position, width, height),
+                _ => continue,
+            };
+            let size = (width.min(height) / 4) as i32;
+
+            let hwnd = unsafe {
+                CreateWindowExW(
+                    WS_EX_LAYERED
+                        | WS_EX_TOPMOST
+                        | WS_EX_TOOLWINDOW
+                        | WS_EX_TRANSPARENT
+                        | WS_EX_NOACTIVATE,
+                    class_name.as_ptr(),
+                    class_name.as_ptr(),
+                    WS_POPUP,
+                    position.x,
+                    position.y,
+                    size,
+                    size,
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                    hinstance,
+                    ptr::null_mut(),
+                )
+            };
+            if hwnd.is_null() {
+                continue;
+            }
+
+            unsafe {
+                SetWindowLongPtrW(hwnd, GWLP_USERDATA, i as isize + 1);
+                SetLayeredWindowAttributes(hwnd, 0, 200, LWA_ALPHA);
+                ShowWindow(hwnd, SW_SHOWNOACTIVATE);
+            }
+            windows.push(hwnd);
+        }
+
+        if let Some(&first) = windows.first() {
+            let millis = duration.as_millis().min(u32::MAX as u128) as u32;
+            unsafe { SetTimer(first, TIMER_ID, millis, None) };
+
+            let mut msg: MSG = unsafe { mem::zeroed() };
+            while unsafe { GetMessageW(&mut msg, ptr::null_mut(), 0, 0) } > 0 {
+                unsafe {
+                    TranslateMessage(&msg);
+                    DispatchMessageW(&msg);
+                }
+            }
+
+            unsafe { KillTimer(first, TIMER_ID) };
+        }
+
+        for hwnd in windows {
+            unsafe { DestroyWindow(hwnd) };
+        }
+    }
+}
+
+unsafe extern "system" fn window_proc(
+    hwnd: HWND,
+    msg: UINT,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    match msg {
+        WM_PAINT => {
+            let mut paint: PAINTSTRUCT = mem::zeroed();
+            let hdc = BeginPaint(hwnd, &mut paint);
+
+            let mut rect: RECT = mem::zeroed();
+            GetClientRect(hwnd, &mut rect);
+            FillRect(hdc, &rect, GetStockObject(BLACK_BRUSH as i32) as _);
+
+            let face = to_wide("Segoe UI");
+            let font = CreateFontW(
+                (rect.bottom - rect.top) * 3 / 4,
+                0,
+                0,
+                0,
+                FW_BOLD,
+                0,
+                0,
+                0,
+                DEFAULT_CHARSET,
+                0,
+                0,
+                0,
+                0,
+                face.as_ptr(),
+            );
+            let old_font = SelectObject(hdc, font as _);
+            SetTextColor(hdc, RGB(255, 255, 255));
+            SetBkMode(hdc, TRANSPARENT as i32);
+
+            let text = to_wide(&GetWindowLongPtrW(hwnd, GWLP_USERDATA).to_string());
+            DrawTextW(
+                hdc,
+                text.as_ptr(),
+                -1,
+                &mut rect,
+                DT_CENTER | DT_VCENTER | DT_SINGLELINE,
+            );
+
+            SelectObject(hdc, old_font);
+            DeleteObject(font as _);
+            EndPaint(hwnd, &paint);
+            0
+        }
+        WM_TIMER if wparam == TIMER_ID => {
+            PostQuitMessage(0);
+            0
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
@@ -0,0 +1,250 @@
+use std::{collections::BTreeMap, convert::TryFrom};
+
+use crate::{DisplayAdapters, Point, Rect};
+
+/// The positions and resolutions of a set of displays, keyed by adapter name.
+///
+/// The adapter `id` can't serve as the key, since all outputs of a GPU share the same one.
+///
+/// Windows rejects or silently fixes up desktops where displays overlap or don't touch, so this
+/// can be used to check a layout before applying it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Layout {
+    displays: BTreeMap<String, (Point, (u32, u32))>,
+}
+
+impl Layout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The current layout of the active adapters.
+    pub fn from_adapters(adapters: &DisplayAdapters) -> Self {
+        let mut layout = Self::new();
+        for adapter in adapters.active() {
+            let info = adapter.info();
+            if let (Some(position), Some(width), Some(height)) =
+                (info.position, info.pels_width, info.pels_height)
+            {
+                layout.insert(adapter.name.clone(), position, (width, height));
+            }
+        }
+
+        layout
+    }
+
+    pub fn insert(&mut self, adapter: impl Into<String>, position: Point, resolution: (u32, u32)) {
+        self.displays.insert(adapter.into(), (position, resolution));
+    }
+
+    pub fn remove(&mut self, adapter: &str) -> Option<(Point, (u32, u32))> {
+        self.displays.remove(adapter)
+    }
+
+    pub fn get(&self, adapter: &str) -> Option<(Point, (u32, u32))> {
+        self.displays.get(adapter).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Point, (u32, u32))> {
+        self.displays
+            .iter()
+            .map(|(name, &(position, resolution))| (name.as_str(), position, resolution))
+    }
+
+    /// Checks that no two displays overlap, and that every display shares an edge with the rest of
+    /// the desktop. Displays which only touch at a corner aren't connected.
+    pub fn validate(&self) -> Result<(), LayoutError> {
+        let rects: Vec<_> = self
+            .displays
+            .iter()
+            .map(|(name, &(position, resolution))| (name, Rect::new(position, resolution)))
+            .collect();
+
+        for (i, (a_name, a)) in rects.iter().enumerate() {
+            for (b_name, b) in &rects[i + 1..] {
+                if a.overlaps(b) {
+                    return Err(LayoutError::Overlap(a_name.to_string(), b_name.to_string()));
+                }
+            }
+        }
+
+        if rects.is_empty() {
+            return Ok(());
+        }
+
+        // Flood fill from the first display along shared edges.
+        let mut connected = vec![false; rects.len()];
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            if connected[i] {
+                continue;
+            }
+            connected[i] = true;
+
+            for (j, (_, other)) in rects.iter().enumerate() {
+                if !connected[j] && rects[i].1.touches(other) {
+                    stack.push(j);
+                }
+            }
+        }
+
+        match connected.iter().position(|&connected| !connected) {
+            Some(i) => Err(LayoutError::Gap(rects[i].0.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Places the displays next to each other in a single row, top-aligned and in their current
+    /// left-to-right order.
+    ///
+    /// A display positioned at the origin stays there, since that is where Windows puts the
+    /// primary display.
+    ///
+    /// Positions are clamped to the range of `i32` if the displays are too wide to fit.
+    pub fn auto_arrange(&mut self) {
+        // The map is ordered by name, which the stable sort keeps for displays in the same spot.
+        let mut displays: Vec<_> = self.displays.values_mut().collect();
+        displays.sort_by_key(|(position, _)| (position.x, position.y));
+
+        let origin = Point { x: 0, y: 0 };
+        let mut x: i32 = 0;
+        let mut anchor = None;
+        for (position, (width, _)) in &mut displays {
+            if *position == origin {
+                anchor = Some(x);
+            }
+
+            *position = Point { x, y: 0 };
+            x = x.saturating_add(i32::try_from(*width).unwrap_or(i32::MAX));
+        }
+
+        if let Some(anchor) = anchor {
+            for (position, _) in displays {
+                position.x = position.x.saturating_sub(anchor);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LayoutError {
+    /// The two displays overlap.
+    Overlap(String, String),
+    /// The display doesn't share an edge with the rest of the desktop.
+    Gap(String),
+}
+
+impl std::fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Overlap(a, b) => write!(f, "{} overlaps {}", a, b),
+            Self::Gap(name) => write!(f, "{} is not connected to the rest of the desktop", name),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The name, position and resolution of a display.
+    type Display<'a> = (&'a str, (i32, i32), (u32, u32));
+
+    fn layout(displays: &[Display]) -> Layout {
+        let mut layout = Layout::new();
+        for &(name, (x, y), resolution) in displays {
+            layout.insert(name, Point { x, y }, resolution);
+        }
+
+        layout
+    }
+
+    #[test]
+    fn validate_side_by_side() {
+        let layout = layout(&[
+            ("a", (0, 0), (1920, 1080)),
+            ("b", (1920, 200), (1280, 1024)),
+            ("c", (-1080, -500), (1080, 1920)),
+        ]);
+
+        assert_eq!(layout.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_overlap() {
+        let layout = layout(&[("a", (0, 0), (1920, 1080)), ("b", (1919, 0), (1920, 1080))]);
+
+        assert_eq!(
+            layout.validate(),
+            Err(LayoutError::Overlap("a".to_string(), "b".to_string()))
+        );
+    }
+
+    #[test]
+    fn validate_corner_contact_is_a_gap() {
+        let layout = layout(&[
+            ("a", (0, 0), (1920, 1080)),
+            ("b", (1920, 1080), (1920, 1080)),
+        ]);
+
+        assert_eq!(layout.validate(), Err(LayoutError::Gap("b".to_string())));
+    }
+
+    #[test]
+    fn validate_disconnected_island() {
+        // `c` and `d` touch each other, but not `a` and `b`.
+        let layout = layout(&[
+            ("a", (0, 0), (1920, 1080)),
+            ("b", (1920, 0), (1920, 1080)),
+            ("c", (5000, 0), (1920, 1080)),
+            ("d", (6920, 0), (1920, 1080)),
+        ]);
+
+        assert_eq!(layout.validate(), Err(LayoutError::Gap("c".to_string())));
+    }
+
+    #[test]
+    fn auto_arrange_keeps_origin_display() {
+        let mut layout = layout(&[
+            ("a", (-3000, 400), (1280, 1024)),
+            ("b", (0, 0), (1920, 1080)),
+            ("c", (4000, -200), (2560, 1440)),
+        ]);
+
+        layout.auto_arrange();
+
+        assert_eq!(
+            layout.get("a"),
+            Some((Point { x: -1280, y: 0 }, (1280, 1024)))
+        );
+        assert_eq!(layout.get("b"), Some((Point { x: 0, y: 0 }, (1920, 1080))));
+        assert_eq!(
+            layout.get("c"),
+            Some((Point { x: 1920, y: 0 }, (2560, 1440)))
+        );
+        assert_eq!(layout.validate(), Ok(()));
+    }
+
+    #[test]
+    fn auto_arrange_huge_widths() {
+        let mut layout = layout(&[
+            ("a", (0, 0), (u32::MAX, u32::MAX)),
+            ("b", (1, 0), (u32::MAX, 1080)),
+            ("c", (2, 0), (1920, 1080)),
+        ]);
+
+        layout.auto_arrange();
+
+        assert_eq!(
+            layout.get("b"),
+            Some((Point { x: i32::MAX, y: 0 }, (u32::MAX, 1080)))
+        );
+        assert_eq!(
+            layout.get("c"),
+            Some((Point { x: i32::MAX, y: 0 }, (1920, 1080)))
+        );
+        assert!(layout.validate().is_err());
+    }
+}
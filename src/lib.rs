@@ -1,49 +1,148 @@
-use std::mem;
+use std::{
+    mem,
+    ops::{Add, Sub},
+};
 
 use winapi::{
-    shared::windef::POINTL,
+    shared::{
+        minwindef::{BOOL, FALSE, LPARAM, TRUE},
+        windef::{
+            DPI_AWARENESS_CONTEXT, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE,
+            DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, DPI_AWARENESS_CONTEXT_SYSTEM_AWARE,
+            DPI_AWARENESS_CONTEXT_UNAWARE, DPI_AWARENESS_CONTEXT_UNAWARE_GDISCALED, HDC, HMONITOR,
+            LPRECT, POINTL,
+        },
+        winerror::S_OK,
+    },
     um::{
+        errhandlingapi::{GetLastError, SetLastError},
+        shellscalingapi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI},
+        winbase::{FormatMessageW, FORMAT_MESSAGE_FROM_SYSTEM, FORMAT_MESSAGE_IGNORE_INSERTS},
         wingdi::{
-            DEVMODEW, DISPLAY_DEVICEW, DISPLAY_DEVICE_ACTIVE, DISPLAY_DEVICE_MIRRORING_DRIVER,
-            DISPLAY_DEVICE_MODESPRUNED, DISPLAY_DEVICE_PRIMARY_DEVICE, DISPLAY_DEVICE_REMOVABLE,
-            DISPLAY_DEVICE_VGA_COMPATIBLE, DMDO_180, DMDO_270, DMDO_90, DMDO_DEFAULT,
-            DM_BITSPERPEL, DM_COLLATE, DM_COLOR, DM_COPIES, DM_DEFAULTSOURCE,
-            DM_DISPLAYFIXEDOUTPUT, DM_DISPLAYFLAGS, DM_DISPLAYFREQUENCY, DM_DISPLAYORIENTATION,
-            DM_DITHERTYPE, DM_DUPLEX, DM_FORMNAME, DM_ICMINTENT, DM_ICMMETHOD, DM_INTERLACED,
-            DM_LOGPIXELS, DM_MEDIATYPE, DM_NUP, DM_ORIENTATION, DM_PANNINGHEIGHT, DM_PANNINGWIDTH,
-            DM_PAPERLENGTH, DM_PAPERSIZE, DM_PAPERWIDTH, DM_PELSHEIGHT, DM_PELSWIDTH, DM_POSITION,
-            DM_PRINTQUALITY, DM_SCALE, DM_TTOPTION, DM_YRESOLUTION,
+            DEVMODEW, DISPLAY_DEVICEW, DISPLAY_DEVICE_ACTIVE, DISPLAY_DEVICE_ATTACHED,
+            DISPLAY_DEVICE_MIRRORING_DRIVER, DISPLAY_DEVICE_MODESPRUNED,
+            DISPLAY_DEVICE_PRIMARY_DEVICE, DISPLAY_DEVICE_REMOTE, DISPLAY_DEVICE_REMOVABLE,
+            DISPLAY_DEVICE_VGA_COMPATIBLE, DMDFO_CENTER, DMDFO_DEFAULT, DMDFO_STRETCH, DMDO_180,
+            DMDO_270, DMDO_90, DMDO_DEFAULT, DM_BITSPERPEL, DM_COLLATE, DM_COLOR, DM_COPIES,
+            DM_DEFAULTSOURCE, DM_DISPLAYFIXEDOUTPUT, DM_DISPLAYFLAGS, DM_DISPLAYFREQUENCY,
+            DM_DISPLAYORIENTATION, DM_DITHERTYPE, DM_DUPLEX, DM_FORMNAME, DM_ICMINTENT,
+            DM_ICMMETHOD, DM_INTERLACED, DM_LOGPIXELS, DM_MEDIATYPE, DM_NUP, DM_ORIENTATION,
+            DM_PANNINGHEIGHT, DM_PANNINGWIDTH, DM_PAPERLENGTH, DM_PAPERSIZE, DM_PAPERWIDTH,
+            DM_PELSHEIGHT, DM_PELSWIDTH, DM_POSITION, DM_PRINTQUALITY, DM_SCALE, DM_SPECVERSION,
+            DM_TTOPTION, DM_YRESOLUTION,
         },
         winuser::{
-            ChangeDisplaySettingsW, EnumDisplayDevicesW, EnumDisplaySettingsW, CDS_FULLSCREEN,
+            ChangeDisplaySettingsExW, ChangeDisplaySettingsW, EnumDisplayDevicesW,
+            EnumDisplayMonitors, EnumDisplaySettingsW, GetMonitorInfoW, GetSystemMetrics,
+            SetProcessDpiAwarenessContext, CDS_NORESET, CDS_TEST, CDS_UPDATEREGISTRY,
             DISP_CHANGE_BADDUALVIEW, DISP_CHANGE_BADFLAGS, DISP_CHANGE_BADMODE,
             DISP_CHANGE_BADPARAM, DISP_CHANGE_FAILED, DISP_CHANGE_NOTUPDATED, DISP_CHANGE_RESTART,
-            DISP_CHANGE_SUCCESSFUL, ENUM_CURRENT_SETTINGS, ENUM_REGISTRY_SETTINGS,
+            DISP_CHANGE_SUCCESSFUL, EDD_GET_DEVICE_INTERFACE_NAME, ENUM_CURRENT_SETTINGS,
+            ENUM_REGISTRY_SETTINGS, MONITORINFO, MONITORINFOEXW, SM_REMOTESESSION,
         },
     },
 };
 
+mod ccd;
+mod change;
+mod color;
+mod d3dkmt;
+mod ddc;
+#[cfg(feature = "dxgi")]
+mod dxgi;
+mod edid;
+mod identify;
+mod layout;
+mod lookup;
+mod profile;
+mod rotation;
+mod snapshot;
+mod watcher;
+
+pub use ccd::{
+    all_monitors, apply_database_config, query_display_config, set_topology, set_topology_and_save,
+    AdapterId, DesktopImageInfo, DisplayTarget, Mode, ModeInfo, PathInfo, PathSourceInfo,
+    PathTargetInfo, Rational, SourceMode, TargetMode, Topology,
+};
+pub use change::{DisplayConfig, SettingsChange, TemporaryChange};
+pub use color::ColorProfileError;
+pub use d3dkmt::{GpuError, GpuIdentity};
+pub use ddc::{Capabilities, DdcError, PhysicalMonitor, PhysicalMonitors, VcpFeature, VcpValue};
+pub use layout::{Layout, LayoutError};
+pub use lookup::{
+    monitor_from_point, monitor_from_window, nearest_monitor, screen_monitors, ScreenMonitor,
+};
+pub use profile::{DisplaySettings, MatchReport, Profile, ProfileToggle};
+pub use rotation::{
+    get_auto_rotation, set_auto_rotation, AutoRotationError, OrientationPreference,
+};
+pub use snapshot::{AdapterSnapshot, DisplayChange, MonitorSnapshot, SystemSnapshot};
+pub use watcher::{
+    global_watcher, DisplayChangeEvent, DisplayWatcher, DisplayWatcherHandle, Subscription,
+};
+
+/// The maximum number of adapters, or monitors per adapter, which are enumerated.
+///
+/// `EnumDisplayDevicesW` is called with increasing indices until it fails, so this guards against
+/// drivers for which it never does.
+const MAX_DEVICES: u32 = 256;
+
 pub struct DisplayAdapters {
     adapters: Vec<DisplayAdapter>,
 }
 
 impl DisplayAdapters {
     pub fn new() -> Option<Self> {
-        let mut adapters = Vec::new();
+        Self::try_new().ok()
+    }
 
-        for i in 0.. {
-            if let Some(adapter) = DisplayAdapter::nth(i) {
-                adapters.push(adapter);
-            } else {
-                break;
+    /// Like `new`, but tells why no adapters could be enumerated.
+    pub fn try_new() -> Result<Self, EnumError> {
+        let mut adapters = Self {
+            adapters: Vec::new(),
+        };
+        // `EnumDisplayDevicesW` doesn't always set the last error, so clear any earlier one.
+        unsafe { SetLastError(0) };
+        adapters.refresh();
+
+        if adapters.adapters.is_empty() {
+            match last_error() {
+                0 => Err(EnumError::NoAdapters),
+                code => {
+                    log::debug!("failed to enumerate display adapters: {}", last_os_error());
+                    Err(EnumError::Failed(code))
+                }
             }
+        } else {
+            Ok(adapters)
         }
+    }
 
-        if adapters.is_empty() {
-            None
-        } else {
-            Some(Self { adapters })
+    /// Enumerates the adapters again, reusing the existing allocation.
+    pub fn refresh(&mut self) {
+        #[cfg(feature = "tracing")]
+        let span =
+            tracing::debug_span!("enumerate_adapters", count = tracing::field::Empty).entered();
+
+        self.adapters.clear();
+
+        for i in 0..MAX_DEVICES {
+            let adapter = match DisplayAdapter::nth(i) {
+                Some(adapter) => adapter,
+                None => break,
+            };
+
+            // A misbehaving driver can keep returning the same device instead of failing.
+            if self.adapters.iter().any(|other| other.name == adapter.name) {
+                log::warn!("display adapter {} was enumerated twice", adapter.name);
+                break;
+            }
+
+            self.adapters.push(adapter);
         }
+
+        #[cfg(feature = "tracing")]
+        span.record("count", self.adapters.len());
     }
 
     pub fn nth(&self, n: usize) -> Option<&DisplayAdapter> {
@@ -56,9 +155,175 @@ impl DisplayAdapters {
             .filter(|adapter| adapter.state.active())
     }
 
+    /// The active adapters, minus pseudo-displays like those of screen recorders and remote
+    /// desktop software.
+    pub fn physical(&self) -> impl Iterator<Item = &DisplayAdapter> {
+        self.adapters
+            .iter()
+            .filter(|adapter| adapter.state.active() && !adapter.state.mirroring_driver())
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &DisplayAdapter> {
         self.adapters.iter()
     }
+
+    /// Lazily enumerates the modes of every adapter, paired with the adapter.
+    pub fn all_modes(&self) -> impl Iterator<Item = (&DisplayAdapter, DisplayMode)> {
+        self.adapters
+            .iter()
+            .flat_map(|adapter| adapter.modes().map(move |mode| (adapter, mode)))
+    }
+
+    /// The resolutions which every active adapter supports, largest first. Useful for finding a
+    /// resolution for clone mode.
+    pub fn common_resolutions(&self) -> Vec<(u32, u32)> {
+        let mut common: Option<Vec<(u32, u32)>> = None;
+        for adapter in self.active() {
+            let mut resolutions: Vec<(u32, u32)> = adapter
+                .modes()
+                .map(|mode| (mode.width, mode.height))
+                .collect();
+            resolutions.sort_unstable();
+            resolutions.dedup();
+
+            common = Some(match common {
+                Some(common) => common
+                    .into_iter()
+                    .filter(|resolution| resolutions.binary_search(resolution).is_ok())
+                    .collect(),
+                None => resolutions,
+            });
+        }
+
+        let mut common = common.unwrap_or_default();
+        common.sort_unstable_by_key(|&(width, height)| {
+            std::cmp::Reverse((width as u64 * height as u64, width))
+        });
+        common
+    }
+
+    /// The width and height of the smallest rectangle containing all active displays, i.e. the
+    /// size of the virtual screen.
+    pub fn bounding_resolution(&self) -> Option<(u32, u32)> {
+        let bounds = self
+            .active()
+            .filter_map(|adapter| {
+                let info = adapter.info();
+                Some(Rect::new(
+                    info.position?,
+                    (info.pels_width?, info.pels_height?),
+                ))
+            })
+            .reduce(|a, b| a.union(&b))?;
+
+        Some((
+            (bounds.right - bounds.left) as u32,
+            (bounds.bottom - bounds.top) as u32,
+        ))
+    }
+}
+
+/// The number of monitors physically connected to active adapters. Monitors which Windows only
+/// remembers from earlier connections and monitors on mirroring drivers aren't counted.
+///
+/// Monitors are told apart by their device `id`, so a monitor which is listed under several
+/// adapters only counts once. Monitors which show the same image, as in clone mode, are still
+/// separate devices and each count.
+pub fn connected_monitor_count() -> usize {
+    let adapters = match DisplayAdapters::new() {
+        Some(adapters) => adapters,
+        None => return 0,
+    };
+
+    let mut ids: Vec<String> = adapters
+        .physical()
+        .flat_map(|adapter| adapter.monitors().monitors)
+        .filter(Monitor::attached)
+        .map(|monitor| monitor.id)
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    ids.len()
+}
+
+/// The current resolution of the primary display.
+pub fn primary_resolution() -> Option<(u32, u32)> {
+    let adapters = DisplayAdapters::new()?;
+    let info = adapters
+        .active()
+        .find(|adapter| adapter.state.primary_device())?
+        .info();
+
+    Some((info.pels_width?, info.pels_height?))
+}
+
+/// Applies all settings changes which have been written to the registry but not yet applied, such
+/// as those made with `CDS_NORESET`.
+pub fn apply_pending() -> Result<(), SetDisplaySettingsError> {
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!("apply_pending", result = tracing::field::Empty).entered();
+
+    let ret = unsafe { ChangeDisplaySettingsW(std::ptr::null_mut(), 0) };
+    log::debug!("ChangeDisplaySettingsW returned {}", ret);
+    #[cfg(feature = "tracing")]
+    span.record("result", ret);
+
+    match ret {
+        DISP_CHANGE_SUCCESSFUL => Ok(()),
+        n => Err(SetDisplaySettingsError::from_raw(n)),
+    }
+}
+
+/// The calling thread's last Win32 error code, as returned by `GetLastError`.
+///
+/// This is only meaningful right after a call which failed.
+pub fn last_error() -> u32 {
+    unsafe { GetLastError() }
+}
+
+/// The calling thread's last Win32 error, formatted as `<system message> (os error <code>)`.
+pub fn last_os_error() -> String {
+    let code = last_error();
+    let mut buffer = [0u16; 512];
+
+    let len = unsafe {
+        FormatMessageW(
+            FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS,
+            std::ptr::null(),
+            code,
+            0,
+            buffer.as_mut_ptr(),
+            buffer.len() as u32,
+            std::ptr::null_mut(),
+        )
+    };
+
+    let message = String::from_utf16_lossy(&buffer[..len as usize]);
+    let message = message.trim_end();
+    if message.is_empty() {
+        format!("unknown error (os error {})", code)
+    } else {
+        format!("{} (os error {})", message, code)
+    }
+}
+
+/// Whether the current process runs in a remote desktop session.
+///
+/// In a remote session, the adapters are usually replaced by a single remote display driver, or
+/// are missing entirely on headless servers. Most settings can't be changed there.
+pub fn is_remote_session() -> bool {
+    unsafe { GetSystemMetrics(SM_REMOTESESSION) != 0 }
+}
+
+/// Sets the DPI awareness of the current process.
+///
+/// This has to be called before any DPI queries, otherwise they will report 96 DPI. It fails if the
+/// awareness has already been set, either by an earlier call or by the application manifest. The
+/// reason for a failure can be retrieved with `last_os_error`.
+///
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-setprocessdpiawarenesscontext
+pub fn set_process_dpi_aware(context: DpiAwareness) -> bool {
+    unsafe { SetProcessDpiAwarenessContext(context.as_raw()) != 0 }
 }
 
 pub struct DisplayAdapter {
@@ -75,26 +340,26 @@ impl DisplayAdapter {
         let mut display_adapter: DISPLAY_DEVICEW = unsafe { mem::zeroed() };
         display_adapter.cb = mem::size_of::<DISPLAY_DEVICEW>() as u32;
 
-        let ok = match unsafe {
-            EnumDisplayDevicesW(std::ptr::null(), n, &mut display_adapter, CDS_FULLSCREEN)
-        } {
-            0 => false,
-            1 => true,
-            n => panic!("Invalid bool: {}", n),
-        };
+        let ok = unsafe { EnumDisplayDevicesW(std::ptr::null(), n, &mut display_adapter, 0) } != 0;
         if !ok {
+            log::trace!("no display adapter at index {}", n);
             return None;
         }
 
-        let mut name = String::from_utf16(&display_adapter.DeviceName).unwrap();
-        name.retain(|c| c != '\u{0}');
-        let mut string = String::from_utf16(&display_adapter.DeviceString).unwrap();
-        string.retain(|c| c != '\u{0}');
-        let state = DisplayState::from_bits(display_adapter.StateFlags).unwrap();
-        let mut id = String::from_utf16(&display_adapter.DeviceID).unwrap();
-        id.retain(|c| c != '\u{0}');
-        let mut key = String::from_utf16(&display_adapter.DeviceKey).unwrap();
-        key.retain(|c| c != '\u{0}');
+        let name = string_from_utf16_and_strip_null(&display_adapter.DeviceName);
+        let string = string_from_utf16_and_strip_null(&display_adapter.DeviceString);
+        // Remote display drivers set flags which aren't modeled
+        let state = DisplayState::from_bits_truncate(display_adapter.StateFlags);
+        let id = string_from_utf16_and_strip_null(&display_adapter.DeviceID);
+        let key = string_from_utf16_and_strip_null(&display_adapter.DeviceKey);
+
+        log::trace!(
+            "display adapter {}: {} ({}), state: {}",
+            n,
+            name,
+            string,
+            state
+        );
 
         Some(Self {
             name,
@@ -106,23 +371,331 @@ impl DisplayAdapter {
         })
     }
 
-    pub fn monitors(&self) -> Option<Monitors> {
+    /// The monitors connected to this adapter. An adapter can drive several monitors, or none at
+    /// all.
+    ///
+    /// Monitors are listed even if the adapter isn't attached to the desktop, so an empty list
+    /// means that nothing is connected, not that the adapter is inactive. Use `is_attached` for
+    /// the latter.
+    pub fn monitors(&self) -> Monitors {
         Monitors::new(self)
     }
 
+    /// The `StateFlags` as reported by `EnumDisplayDevicesW`, including bits which `state` doesn't
+    /// model.
+    pub fn raw_state_flags(&self) -> u32 {
+        self.raw.StateFlags
+    }
+
+    /// Whether the adapter is part of the desktop, i.e. whether anything is shown on it.
+    pub fn is_attached(&self) -> bool {
+        self.state.active()
+    }
+
     pub fn info(&self) -> DisplayDeviceInfo {
         DisplayDeviceInfo::new(self)
     }
 
+    /// Lazily enumerates the graphics modes supported by the adapter.
+    ///
+    /// If `DisplayState::modes_pruned` is set, modes the monitor can't display are left out. The
+    /// full list is available from `EnumDisplaySettingsExW` with `EDS_RAWMODE`, but setting such a
+    /// mode may fail or leave the display blank.
+    pub fn modes(&self) -> DisplayModes<'_> {
+        DisplayModes {
+            adapter: self,
+            index: 0,
+        }
+    }
+
+    /// The mode the adapter is currently in, or `None` if it isn't active.
+    pub fn current_mode(&self) -> Option<DisplayMode> {
+        if !self.state.active() {
+            return None;
+        }
+
+        DisplayDeviceInfo::try_get_raw(self).map(|devmode| DisplayMode::from(&devmode))
+    }
+
+    /// The position of the display in virtual screen coordinates, or `None` if it isn't active.
+    ///
+    /// This is cheaper than going through `info`.
+    pub fn position(&self) -> Option<Point> {
+        let devmode = DisplayDeviceInfo::try_get_raw(self)?;
+        if DmFields::from_bits_truncate(devmode.dmFields).contains(DmFields::POSITION) {
+            Some(unsafe { devmode.u1.s2().dmPosition }.into())
+        } else {
+            None
+        }
+    }
+
+    /// Returns the distinct bit depths among the supported modes, in ascending order.
+    pub fn supported_bit_depths(&self) -> Vec<u32> {
+        let mut depths: Vec<u32> = self.modes().map(|mode| mode.bits_per_pel).collect();
+        depths.sort_unstable();
+        depths.dedup();
+        depths
+    }
+
+    /// Finds a supported mode with the given resolution. `None` matches any frequency or bit
+    /// depth.
+    pub fn find_mode(
+        &self,
+        width: u32,
+        height: u32,
+        hz: Option<u32>,
+        bpp: Option<u32>,
+    ) -> Option<DisplayMode> {
+        self.modes().find(|mode| {
+            mode.width == width
+                && mode.height == height
+                && hz.unwrap_or(mode.frequency) == mode.frequency
+                && bpp.unwrap_or(mode.bits_per_pel) == mode.bits_per_pel
+        })
+    }
+
+    /// Returns a copy of the adapter's current `DEVMODEW`.
+    ///
+    /// This hands out the raw `winapi` type as-is, and is meant as an escape hatch for fields which
+    /// aren't modeled by `DisplayDeviceInfo`. Returns `None` if the current settings couldn't be
+    /// retrieved.
+    pub fn raw_devmode(&self) -> Option<DEVMODEW> {
+        DisplayDeviceInfo::try_get_raw(self)
+    }
+
+    /// Does nothing if the orientation is already in effect. Use `change().force()` to apply it
+    /// anyway.
     pub fn set_orientation(
         &self,
         orientation: DisplayOrientation,
+        persistence: Persistence,
+    ) -> Result<(), SetDisplaySettingsError> {
+        self.apply_orientation(orientation, persistence)?
+            .into_result()
+    }
+
+    /// Like `set_orientation`, but reports `DISP_CHANGE_RESTART` as `ApplyResult::RebootRequired`
+    /// instead of as an error.
+    pub fn apply_orientation(
+        &self,
+        orientation: DisplayOrientation,
+        persistence: Persistence,
+    ) -> Result<ApplyResult, SetDisplaySettingsError> {
+        let mut devmode = self.orientation_devmode(orientation)?;
+        if self.is_in_effect(&devmode, persistence) {
+            return Ok(ApplyResult::Applied);
+        }
+
+        self.change_settings(&mut devmode, persistence.flags())
+    }
+
+    /// Does nothing if the resolution is already in effect. Use `change().force()` to apply it
+    /// anyway.
+    pub fn set_resolution(
+        &self,
+        width: u32,
+        height: u32,
+        persistence: Persistence,
     ) -> Result<(), SetDisplaySettingsError> {
-        let mut devmode = DisplayDeviceInfo::get_raw(&self);
+        let mut devmode = self.resolution_devmode(width, height);
+        if self.is_in_effect(&devmode, persistence) {
+            return Ok(());
+        }
+
+        self.change_settings(&mut devmode, persistence.flags())?
+            .into_result()
+    }
+
+    /// Does nothing if the display is already at the position. Use `change().force()` to apply it
+    /// anyway.
+    pub fn set_position(
+        &self,
+        position: Point,
+        persistence: Persistence,
+    ) -> Result<(), SetDisplaySettingsError> {
+        let mut devmode = self.position_devmode(position);
+        if self.is_in_effect(&devmode, persistence) {
+            return Ok(());
+        }
+
+        self.change_settings(&mut devmode, persistence.flags())?
+            .into_result()
+    }
+
+    /// Makes the desktop of the display `width` by `height` pixels, which it pans across if that's
+    /// larger than its resolution. Few drivers support this.
+    pub fn set_panning(
+        &self,
+        width: u32,
+        height: u32,
+        persistence: Persistence,
+    ) -> Result<(), SetDisplaySettingsError> {
+        let mut devmode = DisplayDeviceInfo::get_raw(self);
+
+        devmode.dmFields = (DmFields::PANNINGWIDTH | DmFields::PANNINGHEIGHT).bits();
+        devmode.dmPanningWidth = width;
+        devmode.dmPanningHeight = height;
+
+        self.change_settings(&mut devmode, persistence.flags())?
+            .into_result()
+    }
+
+    /// Switches to the next larger or smaller supported resolution at the current frequency, bit
+    /// depth and orientation. Resolutions are ordered by their area.
+    ///
+    /// At the largest or smallest resolution, this does nothing rather than wrapping around.
+    pub fn step_resolution(
+        &self,
+        direction: Step,
+        persistence: Persistence,
+    ) -> Result<(), SetDisplaySettingsError> {
+        let current = self
+            .current_mode()
+            .ok_or(SetDisplaySettingsError::BadParam)?;
+
+        let key = |(width, height): (u32, u32)| (width as u64 * height as u64, width);
+        let current_key = key((current.width, current.height));
+
+        let mut resolutions: Vec<(u32, u32)> = self
+            .modes()
+            .filter(|mode| {
+                mode.frequency == current.frequency
+                    && mode.bits_per_pel == current.bits_per_pel
+                    && mode.orientation == current.orientation
+            })
+            .map(|mode| (mode.width, mode.height))
+            .collect();
+        resolutions.sort_unstable_by_key(|&resolution| key(resolution));
+        let next = match direction {
+            Step::Up => resolutions.into_iter().find(|&r| key(r) > current_key),
+            Step::Down => resolutions
+                .into_iter()
+                .rev()
+                .find(|&r| key(r) < current_key),
+        };
+
+        match next {
+            Some((width, height)) => self
+                .change()
+                .resolution(width, height)
+                .frequency(current.frequency)
+                .bits_per_pel(current.bits_per_pel)
+                .persistence(persistence)
+                .apply()?
+                .into_result(),
+            None => Ok(()),
+        }
+    }
+
+    /// Shorthand for `set_orientation` with `Persistence::Deferred`.
+    pub fn set_orientation_deferred(
+        &self,
+        orientation: DisplayOrientation,
+    ) -> Result<(), SetDisplaySettingsError> {
+        self.set_orientation(orientation, Persistence::Deferred)
+    }
+
+    /// Shorthand for `set_resolution` with `Persistence::Deferred`.
+    pub fn set_resolution_deferred(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> Result<(), SetDisplaySettingsError> {
+        self.set_resolution(width, height, Persistence::Deferred)
+    }
+
+    /// Shorthand for `set_position` with `Persistence::Deferred`.
+    pub fn set_position_deferred(&self, position: Point) -> Result<(), SetDisplaySettingsError> {
+        self.set_position(position, Persistence::Deferred)
+    }
+
+    /// Returns the orientations the driver would accept, without actually rotating the display.
+    ///
+    /// Each orientation is tested with `CDS_TEST`.
+    pub fn supported_orientations(&self) -> Vec<DisplayOrientation> {
+        [
+            DisplayOrientation::Default,
+            DisplayOrientation::Rotate90,
+            DisplayOrientation::Rotate180,
+            DisplayOrientation::Rotate270,
+        ]
+        .iter()
+        .copied()
+        .filter(|&orientation| {
+            let result = self
+                .orientation_devmode(orientation)
+                .and_then(|mut devmode| self.change_settings(&mut devmode, CDS_TEST));
+            matches!(result, Ok(ApplyResult::Applied))
+        })
+        .collect()
+    }
+
+    /// The effective DPI of the monitor as `(x, y)`.
+    ///
+    /// This is always 96 unless the process is DPI aware. See `set_process_dpi_aware`.
+    pub fn dpi(&self) -> Option<(u32, u32)> {
+        let hmonitor = self.hmonitor()?;
+        let (mut x, mut y) = (0, 0);
+
+        let hr = unsafe { GetDpiForMonitor(hmonitor, MDT_EFFECTIVE_DPI, &mut x, &mut y) };
+
+        if hr == S_OK {
+            Some((x, y))
+        } else {
+            None
+        }
+    }
+
+    /// The scale factor of the monitor, as in 1.0, 1.25, 1.5, etc.
+    pub fn scale_factor(&self) -> Option<f32> {
+        self.dpi().map(|dpi| dpi.0 as f32 / 96.0)
+    }
+
+    fn hmonitor(&self) -> Option<HMONITOR> {
+        unsafe extern "system" fn callback(
+            hmonitor: HMONITOR,
+            _: HDC,
+            _: LPRECT,
+            data: LPARAM,
+        ) -> BOOL {
+            let (name, found) = &mut *(data as *mut (&str, Option<HMONITOR>));
+
+            let mut info: MONITORINFOEXW = mem::zeroed();
+            info.cbSize = mem::size_of::<MONITORINFOEXW>() as u32;
+            if GetMonitorInfoW(hmonitor, &mut info as *mut _ as *mut MONITORINFO) != 0
+                && string_from_utf16_and_strip_null(&info.szDevice) == *name
+            {
+                *found = Some(hmonitor);
+                return FALSE;
+            }
+
+            TRUE
+        }
+
+        let mut data = (self.name.as_str(), None);
+        unsafe {
+            EnumDisplayMonitors(
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                Some(callback),
+                &mut data as *mut _ as LPARAM,
+            )
+        };
+
+        data.1
+    }
+
+    fn orientation_devmode(
+        &self,
+        orientation: DisplayOrientation,
+    ) -> Result<DEVMODEW, SetDisplaySettingsError> {
+        let mut devmode = DisplayDeviceInfo::get_raw(self);
         let mut fields = DmFields::DISPLAYORIENTATION;
 
+        // Without the current orientation, we can't tell whether the resolution must be swapped.
         let current_orientation =
-            DisplayOrientation::from_raw(unsafe { devmode.u1.s2().dmDisplayOrientation }).unwrap();
+            DisplayOrientation::from_raw(unsafe { devmode.u1.s2().dmDisplayOrientation })
+                .ok_or(SetDisplaySettingsError::Failed)?;
 
         if current_orientation.is_90_or_270_relative(orientation) {
             mem::swap(&mut devmode.dmPelsWidth, &mut devmode.dmPelsHeight);
@@ -132,11 +705,276 @@ impl DisplayAdapter {
         devmode.dmFields = fields.bits();
         unsafe { devmode.u1.s2_mut() }.dmDisplayOrientation = orientation.as_raw();
 
-        // TODO: Parametrize the `dwFlags` argument
-        let ret = unsafe { ChangeDisplaySettingsW(&mut devmode, 0) };
+        Ok(devmode)
+    }
+
+    fn resolution_devmode(&self, width: u32, height: u32) -> DEVMODEW {
+        let mut devmode = DisplayDeviceInfo::get_raw(self);
+
+        devmode.dmFields = (DmFields::PELSWIDTH | DmFields::PELSHEIGHT).bits();
+        devmode.dmPelsWidth = width;
+        devmode.dmPelsHeight = height;
+
+        devmode
+    }
+
+    fn position_devmode(&self, position: Point) -> DEVMODEW {
+        let mut devmode = DisplayDeviceInfo::get_raw(self);
+
+        devmode.dmFields = DmFields::POSITION.bits();
+        unsafe { devmode.u1.s2_mut() }.dmPosition = position.into();
+
+        devmode
+    }
+
+    fn settings_devmode(&self, settings: &DisplaySettings) -> DEVMODEW {
+        let mut devmode = DisplayDeviceInfo::get_raw(self);
+
+        devmode.dmFields = (DmFields::POSITION
+            | DmFields::PELSWIDTH
+            | DmFields::PELSHEIGHT
+            | DmFields::BITSPERPEL
+            | DmFields::DISPLAYFREQUENCY
+            | DmFields::DISPLAYORIENTATION)
+            .bits();
+        devmode.dmPelsWidth = settings.mode.width;
+        devmode.dmPelsHeight = settings.mode.height;
+        devmode.dmBitsPerPel = settings.mode.bits_per_pel;
+        devmode.dmDisplayFrequency = settings.mode.frequency;
+        let fields = unsafe { devmode.u1.s2_mut() };
+        fields.dmPosition = settings.position.into();
+        fields.dmDisplayOrientation = settings.mode.orientation.as_raw();
+
+        devmode
+    }
+
+    fn detach_devmode(&self) -> DEVMODEW {
+        let mut devmode = DisplayDeviceInfo::get_raw(self);
+
+        devmode.dmFields = (DmFields::POSITION | DmFields::PELSWIDTH | DmFields::PELSHEIGHT).bits();
+        devmode.dmPelsWidth = 0;
+        devmode.dmPelsHeight = 0;
+        unsafe { devmode.u1.s2_mut() }.dmPosition = Point { x: 0, y: 0 }.into();
+
+        devmode
+    }
+
+    /// Detaches the adapter from the desktop, turning its display off.
+    ///
+    /// This saves an empty mode to the registry, so the previous mode and position are lost. See
+    /// `enable`.
+    ///
+    /// Fails with `SetDisplaySettingsError::WouldDisableLastDisplay` if this is the only active
+    /// display.
+    pub fn detach(&self) -> Result<(), SetDisplaySettingsError> {
+        self.ensure_other_display_active()?;
+        self.detach_force()
+    }
+
+    /// Like `detach`, but also detaches the last active display.
+    pub fn detach_force(&self) -> Result<(), SetDisplaySettingsError> {
+        let mut devmode = self.detach_devmode();
+
+        self.change_settings_unchecked(&mut devmode, CDS_NORESET | CDS_UPDATEREGISTRY)?
+            .into_result()?;
+        apply_pending()
+    }
+
+    /// Whether the fields selected in `devmode` already match the current settings, so that
+    /// applying it wouldn't change anything. Settings other than the mode and position are never
+    /// considered current.
+    fn is_current(&self, devmode: &DEVMODEW) -> bool {
+        self.matches_settings(devmode, ENUM_CURRENT_SETTINGS)
+    }
+
+    /// Like `is_current`, but a persistent change is only redundant if the registry matches as
+    /// well. Deferred changes are never considered redundant, since they're meant to be applied
+    /// together with others.
+    fn is_in_effect(&self, devmode: &DEVMODEW, persistence: Persistence) -> bool {
+        match persistence {
+            Persistence::Dynamic => self.is_current(devmode),
+            Persistence::Persistent => {
+                self.is_current(devmode) && self.matches_settings(devmode, ENUM_REGISTRY_SETTINGS)
+            }
+            Persistence::Deferred => false,
+        }
+    }
+
+    /// `mode` is either `ENUM_CURRENT_SETTINGS` or `ENUM_REGISTRY_SETTINGS`.
+    fn matches_settings(&self, devmode: &DEVMODEW, mode: u32) -> bool {
+        let current = match DisplayDeviceInfo::try_get_raw_for(self, mode) {
+            Some(current) => current,
+            None => return false,
+        };
+        let fields = DmFields::from_bits_truncate(devmode.dmFields);
+        let comparable = DmFields::PELSWIDTH
+            | DmFields::PELSHEIGHT
+            | DmFields::BITSPERPEL
+            | DmFields::DISPLAYFREQUENCY
+            | DmFields::DISPLAYORIENTATION
+            | DmFields::POSITION;
+        if !comparable.contains(fields) {
+            return false;
+        }
+
+        let (new, old) = unsafe { (devmode.u1.s2(), current.u1.s2()) };
+        (!fields.contains(DmFields::PELSWIDTH) || devmode.dmPelsWidth == current.dmPelsWidth)
+            && (!fields.contains(DmFields::PELSHEIGHT)
+                || devmode.dmPelsHeight == current.dmPelsHeight)
+            && (!fields.contains(DmFields::BITSPERPEL)
+                || devmode.dmBitsPerPel == current.dmBitsPerPel)
+            && (!fields.contains(DmFields::DISPLAYFREQUENCY)
+                || devmode.dmDisplayFrequency == current.dmDisplayFrequency)
+            && (!fields.contains(DmFields::DISPLAYORIENTATION)
+                || new.dmDisplayOrientation == old.dmDisplayOrientation)
+            && (!fields.contains(DmFields::POSITION)
+                || (new.dmPosition.x, new.dmPosition.y) == (old.dmPosition.x, old.dmPosition.y))
+    }
+
+    /// Attaches a detached adapter to the desktop, with the mode and position saved in the
+    /// registry.
+    ///
+    /// If the registry has no usable mode, the best supported mode is used instead, and the
+    /// display is placed to the right of the others. This is always the case after `detach`,
+    /// which has to save an empty mode to turn the display off. To restore a display exactly,
+    /// capture a `Profile` or `SystemSnapshot` before detaching it.
+    ///
+    /// Windows only attaches displays through the registry, so `Persistence::Dynamic` acts like
+    /// `Persistence::Persistent` here.
+    pub fn enable(&self, persistence: Persistence) -> Result<(), SetDisplaySettingsError> {
+        let mut devmode = DisplayDeviceInfo::try_get_raw_for(self, ENUM_REGISTRY_SETTINGS)
+            .filter(|devmode| devmode.dmPelsWidth != 0 && devmode.dmPelsHeight != 0)
+            .or_else(|| {
+                let mode = best(self.modes())?;
+                let right = DisplayAdapters::new()?
+                    .active()
+                    .filter_map(|adapter| {
+                        let info = adapter.info();
+                        Some(info.position?.x + info.pels_width? as i32)
+                    })
+                    .max()
+                    .unwrap_or(0);
+
+                let mut devmode = DisplayDeviceInfo::get_raw(self);
+                devmode.dmPelsWidth = mode.width;
+                devmode.dmPelsHeight = mode.height;
+                devmode.dmBitsPerPel = mode.bits_per_pel;
+                devmode.dmDisplayFrequency = mode.frequency;
+                unsafe { devmode.u1.s2_mut() }.dmPosition = Point { x: right, y: 0 }.into();
+                Some(devmode)
+            })
+            .ok_or(SetDisplaySettingsError::BadMode)?;
+
+        devmode.dmFields = (DmFields::POSITION
+            | DmFields::PELSWIDTH
+            | DmFields::PELSHEIGHT
+            | DmFields::BITSPERPEL
+            | DmFields::DISPLAYFREQUENCY)
+            .bits();
+
+        let persistence = match persistence {
+            Persistence::Dynamic => Persistence::Persistent,
+            persistence => persistence,
+        };
+        self.change_settings(&mut devmode, persistence.flags())?
+            .into_result()
+    }
+
+    /// Checks that some display other than this one would remain active.
+    fn ensure_other_display_active(&self) -> Result<(), SetDisplaySettingsError> {
+        let adapters = DisplayAdapters::new().ok_or(SetDisplaySettingsError::Failed)?;
+        let others = adapters
+            .physical()
+            .filter(|adapter| adapter.name != self.name)
+            .count();
+
+        if others == 0 {
+            Err(SetDisplaySettingsError::WouldDisableLastDisplay)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn set_fixed_output(
+        &self,
+        fixed_output: DisplayFixedOutput,
+        persistence: Persistence,
+    ) -> Result<(), SetDisplaySettingsError> {
+        let mut devmode = DisplayDeviceInfo::get_raw(self);
+
+        devmode.dmFields = DmFields::DISPLAYFIXEDOUTPUT.bits();
+        unsafe { devmode.u1.s2_mut() }.dmDisplayFixedOutput = fixed_output.as_raw();
+
+        self.change_settings(&mut devmode, persistence.flags())?
+            .into_result()
+    }
+
+    /// Applies the fields of `devmode` which are selected in `dmFields`.
+    ///
+    /// A zero width and height detaches the display, which is refused if it's the last active one.
+    fn change_settings(
+        &self,
+        devmode: &mut DEVMODEW,
+        flags: u32,
+    ) -> Result<ApplyResult, SetDisplaySettingsError> {
+        let fields = DmFields::from_bits_truncate(devmode.dmFields);
+        if fields.contains(DmFields::PELSWIDTH | DmFields::PELSHEIGHT)
+            && devmode.dmPelsWidth == 0
+            && devmode.dmPelsHeight == 0
+        {
+            self.ensure_other_display_active()?;
+        }
+
+        self.change_settings_unchecked(devmode, flags)
+    }
+
+    fn change_settings_unchecked(
+        &self,
+        devmode: &mut DEVMODEW,
+        flags: u32,
+    ) -> Result<ApplyResult, SetDisplaySettingsError> {
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(
+            "change_display_settings",
+            adapter = %self.name,
+            flags,
+            result = tracing::field::Empty,
+        )
+        .entered();
+
+        log::debug!(
+            "changing settings of {} with flags {:#x}, fields: {}",
+            self.name,
+            flags,
+            DmFields::from_bits_truncate(devmode.dmFields)
+        );
+        log::trace!(
+            "{}x{} at {:?}, {} bpp, {} Hz, orientation {:?}",
+            devmode.dmPelsWidth,
+            devmode.dmPelsHeight,
+            Point::from(unsafe { devmode.u1.s2().dmPosition }),
+            devmode.dmBitsPerPel,
+            devmode.dmDisplayFrequency,
+            DisplayOrientation::from_raw(unsafe { devmode.u1.s2().dmDisplayOrientation })
+        );
+
+        let ret = unsafe {
+            ChangeDisplaySettingsExW(
+                &self.raw.DeviceName[0],
+                devmode,
+                std::ptr::null_mut(),
+                flags,
+                std::ptr::null_mut(),
+            )
+        };
+        log::debug!("ChangeDisplaySettingsExW returned {}", ret);
+        #[cfg(feature = "tracing")]
+        span.record("result", ret);
 
         match ret {
-            DISP_CHANGE_SUCCESSFUL => Ok(()),
+            DISP_CHANGE_SUCCESSFUL if flags & CDS_NORESET != 0 => Ok(ApplyResult::SavedToRegistry),
+            DISP_CHANGE_SUCCESSFUL => Ok(ApplyResult::Applied),
+            DISP_CHANGE_RESTART => Ok(ApplyResult::RebootRequired),
             n => Err(SetDisplaySettingsError::from_raw(n)),
         }
     }
@@ -160,43 +998,164 @@ impl std::fmt::Debug for DisplayAdapter {
                 let _ = debug_trait_builder.field("state", &&(*__self_0_2));
                 let _ = debug_trait_builder.field("id", &&(*__self_0_3));
                 let _ = debug_trait_builder.field("key", &&(*__self_0_4));
+                let _ = match self.current_mode() {
+                    Some(mode) => debug_trait_builder.field(
+                        "mode",
+                        &format_args!("{}x{}@{}", mode.width, mode.height, mode.frequency),
+                    ),
+                    None => debug_trait_builder.field("mode", &format_args!("inactive")),
+                };
                 debug_trait_builder.finish()
             }
         }
     }
 }
 
+pub struct DisplayModes<'a> {
+    adapter: &'a DisplayAdapter,
+    index: u32,
+}
+
+impl Iterator for DisplayModes<'_> {
+    type Item = DisplayMode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut devmode: DEVMODEW = unsafe { mem::zeroed() };
+        devmode.dmSize = mem::size_of::<DEVMODEW>() as u16;
+
+        let ok = unsafe {
+            EnumDisplaySettingsW(&self.adapter.raw.DeviceName[0], self.index, &mut devmode)
+        };
+        if ok == 0 {
+            return None;
+        }
+
+        self.index += 1;
+        Some(DisplayMode::from(&devmode))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DisplayMode {
+    pub width: u32,
+    pub height: u32,
+    pub bits_per_pel: u32,
+    pub frequency: u32,
+    pub orientation: DisplayOrientation,
+}
+
+impl DisplayMode {
+    pub fn new(
+        width: u32,
+        height: u32,
+        bits_per_pel: u32,
+        frequency: u32,
+        orientation: DisplayOrientation,
+    ) -> Self {
+        Self {
+            width,
+            height,
+            bits_per_pel,
+            frequency,
+            orientation,
+        }
+    }
+}
+
+impl From<&DEVMODEW> for DisplayMode {
+    fn from(devmode: &DEVMODEW) -> Self {
+        let fields = DmFields::from_bits_truncate(devmode.dmFields);
+        let orientation = if fields.contains(DmFields::DISPLAYORIENTATION) {
+            DisplayOrientation::from_raw(unsafe { devmode.u1.s2().dmDisplayOrientation })
+        } else {
+            None
+        };
+
+        Self {
+            width: devmode.dmPelsWidth,
+            height: devmode.dmPelsHeight,
+            bits_per_pel: devmode.dmBitsPerPel,
+            frequency: devmode.dmDisplayFrequency,
+            orientation: orientation.unwrap_or(DisplayOrientation::Default),
+        }
+    }
+}
+
+/// Orders modes by pixel count, then frequency, then bit depth. The remaining fields only break
+/// ties, so that the ordering is total.
+impl Ord for DisplayMode {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let key = |mode: &Self| {
+            (
+                mode.width as u64 * mode.height as u64,
+                mode.frequency,
+                mode.bits_per_pel,
+                mode.width,
+                mode.height,
+                mode.orientation.as_raw(),
+            )
+        };
+
+        key(self).cmp(&key(other))
+    }
+}
+
+impl PartialOrd for DisplayMode {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Picks the mode with the highest resolution, then the highest frequency, then the highest bit
+/// depth.
+pub fn best(modes: impl IntoIterator<Item = DisplayMode>) -> Option<DisplayMode> {
+    modes.into_iter().max()
+}
+
+/// The monitors connected to an adapter.
 pub struct Monitors {
     monitors: Vec<Monitor>,
 }
 
 impl Monitors {
-    fn new(adapter: &DisplayAdapter) -> Option<Self> {
+    /// Enumerates the child devices of the adapter, which are the monitors connected to it.
+    fn new(adapter: &DisplayAdapter) -> Self {
         let mut monitors = Vec::new();
 
         let mut display_device: DISPLAY_DEVICEW = unsafe { mem::zeroed() };
         display_device.cb = mem::size_of::<DISPLAY_DEVICEW>() as u32;
 
         let mut i = 0;
-        while match unsafe {
-            EnumDisplayDevicesW(&adapter.raw.DeviceName[0], i, &mut display_device, 0)
-        } {
-            0 => false,
-            1 => true,
-            n => panic!("Invalid bool: {}", n),
-        } {
-            let mut name = String::from_utf16(&display_device.DeviceName).unwrap();
-            name.retain(|c| c != '\u{0}');
-            let mut string = String::from_utf16(&display_device.DeviceString).unwrap();
-            string.retain(|c| c != '\u{0}');
-            let mut id = String::from_utf16(&display_device.DeviceID).unwrap();
-            id.retain(|c| c != '\u{0}');
-            let mut key = String::from_utf16(&display_device.DeviceKey).unwrap();
-            key.retain(|c| c != '\u{0}');
+        while i < MAX_DEVICES
+            && unsafe { EnumDisplayDevicesW(&adapter.raw.DeviceName[0], i, &mut display_device, 0) }
+                != 0
+        {
+            let name = string_from_utf16_and_strip_null(&display_device.DeviceName);
+            let string = string_from_utf16_and_strip_null(&display_device.DeviceString);
+            let state = DisplayState::from_bits_truncate(display_device.StateFlags);
+            let id = string_from_utf16_and_strip_null(&display_device.DeviceID);
+            let key = string_from_utf16_and_strip_null(&display_device.DeviceKey);
+
+            log::trace!(
+                "monitor {} on {}: {} ({}), state: {}",
+                i,
+                adapter.name,
+                name,
+                string,
+                state
+            );
+
+            if monitors.iter().any(|other: &Monitor| other.name == name) {
+                log::warn!("monitor {} was enumerated twice", name);
+                break;
+            }
 
             let monitor = Monitor {
+                adapter_name: adapter.name.clone(),
                 name,
                 string,
+                state,
                 id,
                 key,
                 raw: display_device,
@@ -206,11 +1165,15 @@ impl Monitors {
             i += 1;
         }
 
-        if monitors.is_empty() {
-            None
-        } else {
-            Some(Self { monitors })
-        }
+        Self { monitors }
+    }
+
+    pub fn len(&self) -> usize {
+        self.monitors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.monitors.is_empty()
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &Monitor> {
@@ -219,37 +1182,147 @@ impl Monitors {
 }
 
 pub struct Monitor {
+    /// The `name` of the adapter this monitor is connected to.
+    pub adapter_name: String,
     pub name: String,
     pub string: String,
+    pub state: DisplayState,
     pub id: String,
     pub key: String,
     raw: DISPLAY_DEVICEW,
 }
 
-impl Monitor {}
+impl Monitor {
+    /// Looks up the adapter this monitor is connected to.
+    pub fn adapter(&self) -> Option<DisplayAdapter> {
+        DisplayAdapters::new()?
+            .adapters
+            .into_iter()
+            .find(|adapter| adapter.name == self.adapter_name)
+    }
+
+    /// Whether the monitor is attached to the desktop, as opposed to merely being enumerated.
+    pub fn active(&self) -> bool {
+        self.state.active()
+    }
+
+    /// Whether the monitor is physically connected, as opposed to being remembered from an earlier
+    /// connection.
+    pub fn attached(&self) -> bool {
+        self.monitor_state().attached()
+    }
+
+    /// The monitor-level state flags, which `state` can't represent.
+    pub fn monitor_state(&self) -> MonitorState {
+        MonitorState::from_bits_truncate(self.raw.StateFlags)
+    }
+
+    /// The monitor's device interface path, in the `\\?\DISPLAY#...#{GUID}` form used by the
+    /// SetupAPI and PnP functions.
+    pub fn interface_path(&self) -> Option<String> {
+        let adapter_name = to_wide(&self.adapter_name);
+        let mut display_device: DISPLAY_DEVICEW = unsafe { mem::zeroed() };
+        display_device.cb = mem::size_of::<DISPLAY_DEVICEW>() as u32;
+
+        // With `EDD_GET_DEVICE_INTERFACE_NAME`, `DeviceID` holds the interface path instead.
+        let mut names = Vec::new();
+        let mut i = 0;
+        while i < MAX_DEVICES
+            && unsafe {
+                EnumDisplayDevicesW(
+                    adapter_name.as_ptr(),
+                    i,
+                    &mut display_device,
+                    EDD_GET_DEVICE_INTERFACE_NAME,
+                )
+            } != 0
+        {
+            let name = string_from_utf16_and_strip_null(&display_device.DeviceName);
+            if name == self.name {
+                let path = string_from_utf16_and_strip_null(&display_device.DeviceID);
+                return if path.is_empty() { None } else { Some(path) };
+            }
+
+            if names.contains(&name) {
+                log::warn!("monitor {} was enumerated twice", name);
+                break;
+            }
+            names.push(name);
+
+            i += 1;
+        }
+
+        None
+    }
+}
 
 // This is a slightly modified form of the derived Debug impl from before the `raw` field was added
 impl std::fmt::Debug for Monitor {
     fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
         match *self {
             Monitor {
-                name: ref __self_0_0,
-                string: ref __self_0_1,
-                id: ref __self_0_2,
-                key: ref __self_0_3,
+                adapter_name: ref __self_0_0,
+                name: ref __self_0_1,
+                string: ref __self_0_2,
+                state: ref __self_0_3,
+                id: ref __self_0_4,
+                key: ref __self_0_5,
                 ..
             } => {
                 let mut debug_trait_builder = f.debug_struct("Monitor");
-                let _ = debug_trait_builder.field("name", &&(*__self_0_0));
-                let _ = debug_trait_builder.field("string", &&(*__self_0_1));
-                let _ = debug_trait_builder.field("id", &&(*__self_0_2));
-                let _ = debug_trait_builder.field("key", &&(*__self_0_3));
+                let _ = debug_trait_builder.field("adapter_name", &&(*__self_0_0));
+                let _ = debug_trait_builder.field("name", &&(*__self_0_1));
+                let _ = debug_trait_builder.field("string", &&(*__self_0_2));
+                let _ = debug_trait_builder.field("state", &&(*__self_0_3));
+                let _ = debug_trait_builder.field("id", &&(*__self_0_4));
+                let _ = debug_trait_builder.field("key", &&(*__self_0_5));
                 debug_trait_builder.finish()
             }
         }
     }
 }
 
+macro_rules! impl_flag_names {
+    ($ty:ident { $($flag:ident),* $(,)? }) => {
+        impl $ty {
+            const NAMES: &'static [($ty, &'static str)] = &[$(($ty::$flag, stringify!($flag))),*];
+
+            /// Returns the names of the flags which are set, in declaration order.
+            pub fn names(self) -> impl Iterator<Item = &'static str> {
+                Self::NAMES
+                    .iter()
+                    .filter(move |(flag, _)| self.contains(*flag))
+                    .map(|&(_, name)| name)
+            }
+
+            /// Looks up a single flag by the name `names` uses for it.
+            pub fn from_name(name: &str) -> Option<Self> {
+                Self::NAMES
+                    .iter()
+                    .find(|&&(_, flag_name)| flag_name == name)
+                    .map(|&(flag, _)| flag)
+            }
+        }
+
+        /// Formats the set flags as `FLAG_A | FLAG_B`, or `(empty)` if none are set.
+        impl std::fmt::Display for $ty {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                let mut names = self.names();
+                match names.next() {
+                    Some(first) => {
+                        f.write_str(first)?;
+                        for name in names {
+                            write!(f, " | {}", name)?;
+                        }
+                        Ok(())
+                    }
+                    None => f.write_str("(empty)"),
+                }
+            }
+        }
+    };
+}
+
 bitflags::bitflags! {
     pub struct DisplayState: u32 {
         const ACTIVE = DISPLAY_DEVICE_ACTIVE;
@@ -257,10 +1330,21 @@ bitflags::bitflags! {
         const MODESPRUNED = DISPLAY_DEVICE_MODESPRUNED;
         const PRIMARY_DEVICE = DISPLAY_DEVICE_PRIMARY_DEVICE;
         const REMOVABLE = DISPLAY_DEVICE_REMOVABLE;
+        const REMOTE = DISPLAY_DEVICE_REMOTE;
         const VGA_COMPATIBLE = DISPLAY_DEVICE_VGA_COMPATIBLE;
     }
 }
 
+impl_flag_names!(DisplayState {
+    ACTIVE,
+    MIRRORING_DRIVE,
+    MODESPRUNED,
+    PRIMARY_DEVICE,
+    REMOVABLE,
+    REMOTE,
+    VGA_COMPATIBLE,
+});
+
 impl DisplayState {
     pub fn active(self) -> bool {
         self.contains(Self::ACTIVE)
@@ -269,20 +1353,121 @@ impl DisplayState {
     pub fn primary_device(self) -> bool {
         self.contains(Self::PRIMARY_DEVICE)
     }
+
+    pub fn mirroring_driver(self) -> bool {
+        self.contains(Self::MIRRORING_DRIVE)
+    }
+
+    pub fn removable(self) -> bool {
+        self.contains(Self::REMOVABLE)
+    }
+
+    /// Whether this is the display driver of a remote desktop session.
+    pub fn remote(self) -> bool {
+        self.contains(Self::REMOTE)
+    }
+
+    /// Whether the driver has pruned the modes which the monitor doesn't support, so that
+    /// `DisplayAdapter::modes` doesn't list every mode the adapter can produce.
+    pub fn modes_pruned(self) -> bool {
+        self.contains(Self::MODESPRUNED)
+    }
+}
+
+/// Serializes as a list of flag names, like `["ACTIVE", "PRIMARY_DEVICE"]`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for DisplayState {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.names())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DisplayState {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = DisplayState;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a list of display state flag names")
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Self::Value, A::Error> {
+                let mut state = DisplayState::empty();
+                while let Some(name) = seq.next_element::<std::borrow::Cow<'de, str>>()? {
+                    state |= DisplayState::from_name(&name).ok_or_else(|| {
+                        let names: Vec<_> = DisplayState::all().names().collect();
+                        serde::de::Error::custom(format_args!(
+                            "unknown display state flag `{}`, expected one of {}",
+                            name,
+                            names.join(", ")
+                        ))
+                    })?;
+                }
+
+                Ok(state)
+            }
+        }
+
+        deserializer.deserialize_seq(Visitor)
+    }
+}
+
+bitflags::bitflags! {
+    /// The state of a monitor, as opposed to that of an adapter.
+    ///
+    /// Only `DISPLAY_DEVICE_ACTIVE` and `DISPLAY_DEVICE_ATTACHED` apply to monitors. The latter
+    /// shares its bit with `DISPLAY_DEVICE_MULTI_DRIVER`, which is why `DisplayState` doesn't
+    /// have it.
+    pub struct MonitorState: u32 {
+        /// The monitor is in use by the desktop.
+        const ACTIVE = DISPLAY_DEVICE_ACTIVE;
+        /// The monitor is physically connected. Monitors which Windows merely remembers lack this.
+        const ATTACHED = DISPLAY_DEVICE_ATTACHED;
+    }
+}
+
+impl_flag_names!(MonitorState { ACTIVE, ATTACHED });
+
+impl MonitorState {
+    pub fn active(self) -> bool {
+        self.contains(Self::ACTIVE)
+    }
+
+    pub fn attached(self) -> bool {
+        self.contains(Self::ATTACHED)
+    }
 }
 
 #[derive(Debug)]
 pub struct DisplayDeviceInfo {
     pub name: String,
+    /// The version of the `DEVMODEW` layout the driver filled in, normally `DM_SPECVERSION`.
+    pub spec_version: u16,
     pub driver_version: u16,
 
     pub position: Option<Point>,
     pub orientation: Option<DisplayOrientation>,
+    pub fixed_output: Option<DisplayFixedOutput>,
     pub bits_per_pel: Option<u32>,
     pub pels_width: Option<u32>,
     pub pels_height: Option<u32>,
     pub flags: Option<DisplayFlags>,
+    /// The refresh rate as reported by the driver. 0 and 1 mean the hardware's default rate rather
+    /// than an actual rate; see `frequency_hz`.
     pub frequency: Option<u32>,
+    pub scale: Option<u32>,
+    /// The DPI the desktop is configured for.
+    pub log_pixels: Option<u32>,
+    /// The size of the virtual desktop which the display pans across, if it's larger than the
+    /// display itself.
+    pub panning_width: Option<u32>,
+    pub panning_height: Option<u32>,
 }
 
 impl DisplayDeviceInfo {
@@ -290,10 +1475,19 @@ impl DisplayDeviceInfo {
         let devmode = Self::get_raw(adapter);
 
         let name = string_from_utf16_and_strip_null(&devmode.dmDeviceName);
-        // TODO: Check spec_version
         let spec_version = devmode.dmSpecVersion;
+        // The display fields of the unions are laid out according to this version.
+        if spec_version as u32 != DM_SPECVERSION {
+            log::warn!(
+                "{} reported DEVMODEW spec version {:#x}, expected {:#x}",
+                adapter.name,
+                spec_version,
+                DM_SPECVERSION
+            );
+        }
         let driver_version = devmode.dmDriverVersion;
-        let fields = DmFields::from_bits(devmode.dmFields).unwrap();
+        // Drivers may set bits which are only meaningful for printers.
+        let fields = DmFields::from_bits_truncate(devmode.dmFields);
 
         let struct_2 = unsafe { devmode.u1.s2() };
 
@@ -309,6 +1503,12 @@ impl DisplayDeviceInfo {
             None
         };
 
+        let fixed_output = if fields.contains(DmFields::DISPLAYFIXEDOUTPUT) {
+            DisplayFixedOutput::from_raw(struct_2.dmDisplayFixedOutput)
+        } else {
+            None
+        };
+
         let bits_per_pel = if fields.contains(DmFields::BITSPERPEL) {
             Some(devmode.dmBitsPerPel)
         } else {
@@ -328,7 +1528,9 @@ impl DisplayDeviceInfo {
         };
 
         let flags = if fields.contains(DmFields::DISPLAYFLAGS) {
-            DisplayFlags::from_bits(unsafe { *devmode.u2.dmDisplayFlags() })
+            Some(DisplayFlags::from_bits_truncate(unsafe {
+                *devmode.u2.dmDisplayFlags()
+            }))
         } else {
             None
         };
@@ -339,32 +1541,105 @@ impl DisplayDeviceInfo {
             None
         };
 
+        let scale = if fields.contains(DmFields::SCALE) {
+            Some(unsafe { devmode.u1.s1() }.dmScale as u32)
+        } else {
+            None
+        };
+
+        let log_pixels = if fields.contains(DmFields::LOGPIXELS) {
+            Some(devmode.dmLogPixels as u32)
+        } else {
+            None
+        };
+
+        let panning_width = if fields.contains(DmFields::PANNINGWIDTH) {
+            Some(devmode.dmPanningWidth)
+        } else {
+            None
+        };
+
+        let panning_height = if fields.contains(DmFields::PANNINGHEIGHT) {
+            Some(devmode.dmPanningHeight)
+        } else {
+            None
+        };
+
         Self {
             name,
+            spec_version,
             driver_version,
             position,
             orientation,
+            fixed_output,
             bits_per_pel,
             pels_width,
             pels_height,
             flags,
             frequency,
+            scale,
+            log_pixels,
+            panning_width,
+            panning_height,
+        }
+    }
+
+    /// The resolution of the desktop on the display. For portrait orientations, the width and height
+    /// are swapped compared to the panel.
+    pub fn effective_resolution(&self) -> Option<(u32, u32)> {
+        Some((self.pels_width?, self.pels_height?))
+    }
+
+    /// The resolution in the panel's own, unrotated orientation.
+    pub fn panel_resolution(&self) -> Option<(u32, u32)> {
+        let (width, height) = self.effective_resolution()?;
+        match self.orientation {
+            Some(DisplayOrientation::Rotate90) | Some(DisplayOrientation::Rotate270) => {
+                Some((height, width))
+            }
+            _ => Some((width, height)),
+        }
+    }
+
+    /// Whether the current mode is interlaced. Modes without display flags are progressive.
+    pub fn is_interlaced(&self) -> bool {
+        self.flags
+            .unwrap_or_else(DisplayFlags::empty)
+            .is_interlaced()
+    }
+
+    /// The refresh rate in Hz, or `None` if it's unknown or the hardware's default rate.
+    pub fn frequency_hz(&self) -> Option<u32> {
+        match self.frequency? {
+            0 | 1 => None,
+            frequency => Some(frequency),
         }
     }
 
     fn get_raw(adapter: &DisplayAdapter) -> DEVMODEW {
+        Self::try_get_raw(adapter).unwrap_or_else(|| {
+            let mut devmode: DEVMODEW = unsafe { std::mem::zeroed() };
+            devmode.dmSize = mem::size_of::<DEVMODEW>() as u16;
+            devmode
+        })
+    }
+
+    fn try_get_raw(adapter: &DisplayAdapter) -> Option<DEVMODEW> {
+        Self::try_get_raw_for(adapter, ENUM_CURRENT_SETTINGS)
+    }
+
+    /// `mode` is either `ENUM_CURRENT_SETTINGS` or `ENUM_REGISTRY_SETTINGS`.
+    fn try_get_raw_for(adapter: &DisplayAdapter, mode: u32) -> Option<DEVMODEW> {
         let mut devmode: DEVMODEW = unsafe { std::mem::zeroed() };
         devmode.dmSize = mem::size_of::<DEVMODEW>() as u16;
 
-        unsafe {
-            EnumDisplaySettingsW(
-                &adapter.raw.DeviceName[0],
-                ENUM_CURRENT_SETTINGS,
-                &mut devmode,
-            )
-        };
+        let ok = unsafe { EnumDisplaySettingsW(&adapter.raw.DeviceName[0], mode, &mut devmode) };
 
-        devmode
+        if ok == 0 {
+            None
+        } else {
+            Some(devmode)
+        }
     }
 }
 
@@ -403,12 +1678,68 @@ bitflags::bitflags! {
     }
 }
 
-#[derive(Debug)]
+impl_flag_names!(DmFields {
+    ORIENTATION,
+    PAPERSIZE,
+    PAPERLENGTH,
+    PAPERWIDTH,
+    SCALE,
+    COPIES,
+    DEFAULTSOURCE,
+    PRINTQUALITY,
+    POSITION,
+    DISPLAYORIENTATION,
+    DISPLAYFIXEDOUTPUT,
+    COLOR,
+    DUPLEX,
+    YRESOLUTION,
+    TTOPTION,
+    COLLATE,
+    FORMNAME,
+    LOGPIXELS,
+    BITSPERPEL,
+    PELSWIDTH,
+    PELSHEIGHT,
+    DISPLAYFLAGS,
+    NUP,
+    DISPLAYFREQUENCY,
+    ICMMETHOD,
+    ICMINTENT,
+    MEDIATYPE,
+    DITHERTYPE,
+    PANNINGWIDTH,
+    PANNINGHEIGHT,
+});
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Point {
     pub x: i32,
     pub y: i32,
 }
 
+impl Add for Point {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+impl Sub for Point {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+}
+
 impl From<POINTL> for Point {
     fn from(from: POINTL) -> Self {
         Self {
@@ -418,7 +1749,92 @@ impl From<POINTL> for Point {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+impl From<Point> for POINTL {
+    fn from(from: Point) -> Self {
+        Self {
+            x: from.x,
+            y: from.y,
+        }
+    }
+}
+
+/// A rectangle in virtual screen coordinates. The right and bottom edges are exclusive.
+struct Rect {
+    left: i64,
+    top: i64,
+    right: i64,
+    bottom: i64,
+}
+
+impl Rect {
+    fn new(position: Point, (width, height): (u32, u32)) -> Self {
+        Self {
+            left: position.x as i64,
+            top: position.y as i64,
+            right: position.x as i64 + width as i64,
+            bottom: position.y as i64 + height as i64,
+        }
+    }
+
+    fn from_corners(a: Point, b: Point) -> Self {
+        Self {
+            left: a.x.min(b.x) as i64,
+            top: a.y.min(b.y) as i64,
+            right: a.x.max(b.x) as i64,
+            bottom: a.y.max(b.y) as i64,
+        }
+    }
+
+    /// The smallest rectangle containing both.
+    fn union(&self, other: &Self) -> Self {
+        Self {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+
+    fn overlaps(&self, other: &Self) -> bool {
+        self.intersection_area(other) > 0
+    }
+
+    /// Whether the two share an edge. Touching only at a corner doesn't count.
+    fn touches(&self, other: &Self) -> bool {
+        let horizontal_overlap = self.left.max(other.left) < self.right.min(other.right);
+        let vertical_overlap = self.top.max(other.top) < self.bottom.min(other.bottom);
+
+        (horizontal_overlap && (self.bottom == other.top || other.bottom == self.top))
+            || (vertical_overlap && (self.right == other.left || other.right == self.left))
+    }
+
+    fn intersection_area(&self, other: &Self) -> i64 {
+        let width = self.right.min(other.right) - self.left.max(other.left);
+        let height = self.bottom.min(other.bottom) - self.top.max(other.top);
+        width.max(0).saturating_mul(height.max(0))
+    }
+
+    /// The squared length of the shortest line between the two. Zero if they touch or overlap.
+    fn gap_squared(&self, other: &Self) -> i64 {
+        let dx = (other.left - self.right)
+            .max(self.left - other.right)
+            .max(0);
+        let dy = (other.top - self.bottom)
+            .max(self.top - other.bottom)
+            .max(0);
+        dx.saturating_mul(dx).saturating_add(dy.saturating_mul(dy))
+    }
+
+    /// The squared distance between the centers, doubled along both axes to avoid fractions.
+    fn center_distance_squared(&self, other: &Self) -> i64 {
+        let dx = (self.left + self.right) - (other.left + other.right);
+        let dy = (self.top + self.bottom) - (other.top + other.bottom);
+        dx.saturating_mul(dx).saturating_add(dy.saturating_mul(dy))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DisplayOrientation {
     Default,
     Rotate90,
@@ -447,6 +1863,38 @@ impl DisplayOrientation {
         }
     }
 
+    /// The orientation for a rotation of 0, 90, 180 or 270 degrees, as with the `DMDO_*` values.
+    pub fn from_degrees(degrees: u32) -> Option<Self> {
+        match degrees {
+            0 => Some(Self::Default),
+            90 => Some(Self::Rotate90),
+            180 => Some(Self::Rotate180),
+            270 => Some(Self::Rotate270),
+            _ => None,
+        }
+    }
+
+    pub fn to_degrees(self) -> u32 {
+        match self {
+            Self::Default => 0,
+            Self::Rotate90 => 90,
+            Self::Rotate180 => 180,
+            Self::Rotate270 => 270,
+        }
+    }
+
+    /// The name of the orientation in the Windows display settings.
+    ///
+    /// `Rotate90` is "Portrait" and `Rotate270` is "Portrait (flipped)".
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Default => "Landscape",
+            Self::Rotate90 => "Portrait",
+            Self::Rotate180 => "Landscape (flipped)",
+            Self::Rotate270 => "Portrait (flipped)",
+        }
+    }
+
     pub fn is_90_or_270_relative(self, other: DisplayOrientation) -> bool {
         match self {
             Self::Default | Self::Rotate180 => match other {
@@ -461,16 +1909,173 @@ impl DisplayOrientation {
     }
 }
 
+/// Formats the orientation as `landscape`, `portrait`, `landscape-flipped` or `portrait-flipped`.
+impl std::fmt::Display for DisplayOrientation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Default => "landscape",
+            Self::Rotate90 => "portrait",
+            Self::Rotate180 => "landscape-flipped",
+            Self::Rotate270 => "portrait-flipped",
+        })
+    }
+}
+
+impl std::str::FromStr for DisplayOrientation {
+    type Err = ParseOrientationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "landscape" => Ok(Self::Default),
+            "portrait" => Ok(Self::Rotate90),
+            "landscape-flipped" => Ok(Self::Rotate180),
+            "portrait-flipped" => Ok(Self::Rotate270),
+            _ => Err(ParseOrientationError),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseOrientationError;
+
+impl std::fmt::Display for ParseOrientationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(
+            "expected one of `landscape`, `portrait`, `landscape-flipped` or `portrait-flipped`",
+        )
+    }
+}
+
+impl std::error::Error for ParseOrientationError {}
+
+/// Whether a settings change outlasts the session.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Persistence {
+    /// The change only affects the current session, and is undone by logging off or restarting.
+    /// This is the default.
+    #[default]
+    Dynamic,
+    /// The change is also saved to the registry (`CDS_UPDATEREGISTRY`), so it's restored on the
+    /// next logon.
+    Persistent,
+    /// The change is only saved to the registry (`CDS_UPDATEREGISTRY | CDS_NORESET`), and takes
+    /// effect with the next call to `apply_pending`. This allows changing several displays at
+    /// once.
+    Deferred,
+}
+
+impl Persistence {
+    fn flags(self) -> u32 {
+        match self {
+            Self::Dynamic => 0,
+            Self::Persistent => CDS_UPDATEREGISTRY,
+            Self::Deferred => CDS_UPDATEREGISTRY | CDS_NORESET,
+        }
+    }
+}
+
+/// The direction for `DisplayAdapter::step_resolution`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Step {
+    Up,
+    Down,
+}
+
+/// How a lower resolution mode is presented on a fixed-resolution display, such as an LCD panel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DisplayFixedOutput {
+    Default,
+    Stretch,
+    Center,
+}
+
+impl DisplayFixedOutput {
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            DMDFO_DEFAULT => Some(Self::Default),
+            DMDFO_STRETCH => Some(Self::Stretch),
+            DMDFO_CENTER => Some(Self::Center),
+            _ => None,
+        }
+    }
+
+    pub fn as_raw(self) -> u32 {
+        match self {
+            Self::Default => DMDFO_DEFAULT,
+            Self::Stretch => DMDFO_STRETCH,
+            Self::Center => DMDFO_CENTER,
+        }
+    }
+}
+
+/// https://docs.microsoft.com/en-us/windows/win32/hidpi/dpi-awareness-context
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DpiAwareness {
+    Unaware,
+    SystemAware,
+    PerMonitorAware,
+    PerMonitorAwareV2,
+    UnawareGdiScaled,
+}
+
+impl DpiAwareness {
+    pub fn as_raw(self) -> DPI_AWARENESS_CONTEXT {
+        match self {
+            Self::Unaware => DPI_AWARENESS_CONTEXT_UNAWARE,
+            Self::SystemAware => DPI_AWARENESS_CONTEXT_SYSTEM_AWARE,
+            Self::PerMonitorAware => DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE,
+            Self::PerMonitorAwareV2 => DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+            Self::UnawareGdiScaled => DPI_AWARENESS_CONTEXT_UNAWARE_GDISCALED,
+        }
+    }
+}
+
+// This isn't exposed by winapi 0.3
+const DM_GRAYSCALE: u32 = 0x0000_0001;
+
 bitflags::bitflags! {
     pub struct DisplayFlags: u32 {
-        // FIXME: winapi doesn't seem to define `DM_GRAYSCALE` anywhere
-        // const GRAYSCALE = DM_GRAYSCALE;
+        const GRAYSCALE = DM_GRAYSCALE;
         const INTERLACED = DM_INTERLACED;
     }
 }
 
+impl DisplayFlags {
+    /// Whether the display shows shades of gray rather than color, like some e-ink panels.
+    pub fn is_grayscale(self) -> bool {
+        self.contains(Self::GRAYSCALE)
+    }
+
+    pub fn is_interlaced(self) -> bool {
+        self.contains(Self::INTERLACED)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnumError {
+    /// The enumeration succeeded, but there are no display adapters.
+    NoAdapters,
+    /// `EnumDisplayDevicesW` failed with the contained OS error code.
+    Failed(u32),
+}
+
+impl std::fmt::Display for EnumError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NoAdapters => f.write_str("no display adapters found"),
+            Self::Failed(code) => write!(
+                f,
+                "failed to enumerate display adapters (os error {})",
+                code
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EnumError {}
+
 /// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-changedisplaysettingsw#return-value
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SetDisplaySettingsError {
     BadDualView,
     BadFlags,
@@ -478,7 +2083,20 @@ pub enum SetDisplaySettingsError {
     BadParam,
     Failed,
     NotUpdated,
+    /// The settings were saved, but the computer must be restarted for them to take effect.
+    ///
+    /// This isn't really a failure, and the `apply_*` methods report it as
+    /// `ApplyResult::RebootRequired` instead.
     Restart,
+    /// The display doesn't support the requested feature.
+    NotSupported,
+    /// The adapter has no free source (display controller) left to drive another monitor.
+    NoFreeSource,
+    /// The change would leave no display active. Use `DisplayAdapter::detach_force` or
+    /// `SettingsChange::force` to do it anyway.
+    WouldDisableLastDisplay,
+    /// The system returned an undocumented `DISP_CHANGE_*` code.
+    Unknown(i32),
 }
 
 impl SetDisplaySettingsError {
@@ -491,13 +2109,74 @@ impl SetDisplaySettingsError {
             DISP_CHANGE_FAILED => Self::Failed,
             DISP_CHANGE_NOTUPDATED => Self::NotUpdated,
             DISP_CHANGE_RESTART => Self::Restart,
-            n => panic!("Unexpected error code: {}", n),
+            n => Self::Unknown(n),
+        }
+    }
+}
+
+/// The outcome of a settings change which didn't fail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApplyResult {
+    /// The settings are in effect.
+    Applied,
+    /// The settings were saved, but the computer must be restarted for them to take effect.
+    RebootRequired,
+    /// The settings were only written to the registry, and take effect with the next call to
+    /// `apply_pending`.
+    SavedToRegistry,
+    /// The settings were applied, but the driver substituted a different mode or position. Carries
+    /// the mode which is in effect. Only reported by `SettingsChange::verify`.
+    Substituted(DisplayMode),
+}
+
+impl ApplyResult {
+    fn into_result(self) -> Result<(), SetDisplaySettingsError> {
+        match self {
+            Self::Applied | Self::SavedToRegistry | Self::Substituted(_) => Ok(()),
+            Self::RebootRequired => Err(SetDisplaySettingsError::Restart),
         }
     }
 }
 
+/// Converts a NUL-terminated wide string, such as the fixed-size buffers in winapi structs. Anything
+/// after the first NUL is ignored.
 fn string_from_utf16_and_strip_null(v: &[u16]) -> String {
-    let mut string = String::from_utf16(v).unwrap();
-    string.retain(|c| c != '\u{0}');
-    string
+    let len = v.iter().position(|&c| c == 0).unwrap_or(v.len());
+    String::from_utf16_lossy(&v[..len])
+}
+
+fn to_wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIENTATIONS: [DisplayOrientation; 4] = [
+        DisplayOrientation::Default,
+        DisplayOrientation::Rotate90,
+        DisplayOrientation::Rotate180,
+        DisplayOrientation::Rotate270,
+    ];
+
+    #[test]
+    fn orientation_round_trips_through_string() {
+        for &orientation in &ORIENTATIONS {
+            assert_eq!(orientation.to_string().parse(), Ok(orientation));
+        }
+    }
+
+    #[test]
+    fn orientation_rejects_unknown_string() {
+        assert_eq!(
+            "sideways".parse::<DisplayOrientation>(),
+            Err(ParseOrientationError)
+        );
+        assert_eq!(
+            "Portrait".parse::<DisplayOrientation>(),
+            Err(ParseOrientationError)
+        );
+        assert_eq!("".parse::<DisplayOrientation>(), Err(ParseOrientationError));
+    }
 }
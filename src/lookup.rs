@@ -0,0 +1,158 @@
+use std::mem;
+
+use winapi::{
+    shared::{
+        minwindef::{BOOL, LPARAM, TRUE},
+        windef::{HDC, HMONITOR, HWND, LPRECT, POINT, RECT},
+    },
+    um::winuser::{
+        EnumDisplayMonitors, GetMonitorInfoW, MonitorFromPoint, MonitorFromWindow, MONITORINFO,
+        MONITORINFOEXW, MONITORINFOF_PRIMARY, MONITOR_DEFAULTTONEAREST,
+    },
+};
+
+use crate::{string_from_utf16_and_strip_null, DisplayAdapter, DisplayAdapters, Point, Rect};
+
+/// The adapter of the display the window is on, or the one nearest to it.
+///
+/// `hwnd` is the raw window handle, as handed out by most windowing libraries.
+pub fn monitor_from_window(hwnd: isize) -> Option<DisplayAdapter> {
+    adapter_from_hmonitor(unsafe { MonitorFromWindow(hwnd as HWND, MONITOR_DEFAULTTONEAREST) })
+}
+
+/// The adapter of the display containing the point in virtual screen coordinates, or the one
+/// nearest to it.
+pub fn monitor_from_point(point: Point) -> Option<DisplayAdapter> {
+    let point = POINT {
+        x: point.x,
+        y: point.y,
+    };
+    adapter_from_hmonitor(unsafe { MonitorFromPoint(point, MONITOR_DEFAULTTONEAREST) })
+}
+
+/// The adapter whose display has the largest intersection with the rectangle, given as its
+/// top-left and bottom-right corners in virtual screen coordinates.
+///
+/// If the rectangle doesn't intersect any display, the display closest to it is picked instead.
+/// Remaining ties go to the display whose center is closest to the center of the rectangle, and
+/// after that to the adapter which was enumerated first.
+pub fn nearest_monitor(rect: (Point, Point)) -> Option<DisplayAdapter> {
+    let rect = Rect::from_corners(rect.0, rect.1);
+
+    let adapters = DisplayAdapters::new()?;
+    adapters
+        .adapters
+        .into_iter()
+        .filter(|adapter| adapter.state.active())
+        .filter_map(|adapter| {
+            let info = adapter.info();
+            let position = info.position?;
+            let monitor = Rect::new(position, (info.pels_width?, info.pels_height?));
+
+            let key = (
+                -rect.intersection_area(&monitor),
+                rect.gap_squared(&monitor),
+                rect.center_distance_squared(&monitor),
+            );
+            Some((key, adapter))
+        })
+        .min_by_key(|(key, _)| *key)
+        .map(|(_, adapter)| adapter)
+}
+
+/// A display as the window manager sees it, as returned by `screen_monitors`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScreenMonitor {
+    /// The raw `HMONITOR`. It becomes invalid when the display configuration changes.
+    pub hmonitor: isize,
+    /// The top-left and bottom-right corners of the display in virtual screen coordinates. The
+    /// bottom-right corner is exclusive.
+    pub rect: (Point, Point),
+    /// Like `rect`, but without the taskbar and other docked toolbars.
+    pub work_area: (Point, Point),
+    pub primary: bool,
+    /// The GDI name of the adapter, as in `DisplayAdapter::name`.
+    pub device_name: String,
+}
+
+impl ScreenMonitor {
+    /// Looks up the adapter which drives this display.
+    pub fn adapter(&self) -> Option<DisplayAdapter> {
+        DisplayAdapters::new()?
+            .adapters
+            .into_iter()
+            .find(|adapter| adapter.name == self.device_name)
+    }
+}
+
+/// Lists the displays which are part of the desktop, along with their geometry.
+///
+/// Unlike `DisplayAdapters`, this only includes active displays and doesn't need to query each
+/// adapter's settings, which makes it the better fit for placing windows.
+pub fn screen_monitors() -> Vec<ScreenMonitor> {
+    unsafe extern "system" fn callback(
+        hmonitor: HMONITOR,
+        _: HDC,
+        _: LPRECT,
+        data: LPARAM,
+    ) -> BOOL {
+        let monitors = &mut *(data as *mut Vec<ScreenMonitor>);
+
+        let mut info: MONITORINFOEXW = mem::zeroed();
+        info.cbSize = mem::size_of::<MONITORINFOEXW>() as u32;
+        if GetMonitorInfoW(hmonitor, &mut info as *mut _ as *mut MONITORINFO) != 0 {
+            monitors.push(ScreenMonitor {
+                hmonitor: hmonitor as isize,
+                rect: corners(&info.rcMonitor),
+                work_area: corners(&info.rcWork),
+                primary: info.dwFlags & MONITORINFOF_PRIMARY != 0,
+                device_name: string_from_utf16_and_strip_null(&info.szDevice),
+            });
+        }
+
+        TRUE
+    }
+
+    let mut monitors = Vec::new();
+    unsafe {
+        EnumDisplayMonitors(
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            Some(callback),
+            &mut monitors as *mut _ as LPARAM,
+        )
+    };
+
+    monitors
+}
+
+fn corners(rect: &RECT) -> (Point, Point) {
+    (
+        Point {
+            x: rect.left,
+            y: rect.top,
+        },
+        Point {
+            x: rect.right,
+            y: rect.bottom,
+        },
+    )
+}
+
+fn adapter_from_hmonitor(hmonitor: HMONITOR) -> Option<DisplayAdapter> {
+    if hmonitor.is_null() {
+        return None;
+    }
+
+    let mut info: MONITORINFOEXW = unsafe { mem::zeroed() };
+    info.cbSize = mem::size_of::<MONITORINFOEXW>() as u32;
+    if unsafe { GetMonitorInfoW(hmonitor, &mut info as *mut _ as *mut MONITORINFO) } == 0 {
+        return None;
+    }
+
+    let name = string_from_utf16_and_strip_null(&info.szDevice);
+    DisplayAdapters::new()?
+        .adapters
+        .into_iter()
+        .find(|adapter| adapter.name == name)
+}
@@ -0,0 +1,363 @@
+use std::{cmp::Ordering, ptr};
+
+use winapi::um::winuser::{CDS_NORESET, CDS_UPDATEREGISTRY};
+
+use crate::{
+    apply_pending, DisplayAdapter, DisplayAdapters, DisplayDeviceInfo, DisplayMode, DisplayState,
+    Point, SetDisplaySettingsError, SystemSnapshot,
+};
+
+/// A saved configuration of all active displays.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Profile {
+    pub displays: Vec<DisplaySettings>,
+}
+
+/// The settings of a single display within a `Profile`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisplaySettings {
+    /// The name of the adapter, as in `DisplayAdapter::name`.
+    pub adapter: String,
+    pub position: Point,
+    pub mode: DisplayMode,
+}
+
+impl Profile {
+    /// Captures the current settings of all active adapters.
+    pub fn current() -> Option<Self> {
+        let adapters = DisplayAdapters::new()?;
+
+        let displays = adapters
+            .active()
+            .filter_map(|adapter| {
+                let devmode = DisplayDeviceInfo::try_get_raw(adapter)?;
+                Some(DisplaySettings {
+                    adapter: adapter.name.clone(),
+                    position: unsafe { devmode.u1.s2().dmPosition }.into(),
+                    mode: DisplayMode::from(&devmode),
+                })
+            })
+            .collect();
+
+        Some(Self { displays })
+    }
+
+    /// Compares the displays of the profile against the connected ones.
+    ///
+    /// A display counts as present if its adapter exists and has a monitor connected to it, even if
+    /// the adapter is currently detached.
+    pub fn matches_current(&self) -> MatchReport {
+        let adapters = DisplayAdapters::new();
+        let adapters: Vec<&DisplayAdapter> = adapters.iter().flat_map(|a| a.iter()).collect();
+
+        let mut report = MatchReport::default();
+        for settings in &self.displays {
+            let connected = adapters
+                .iter()
+                .any(|adapter| adapter.name == settings.adapter && !adapter.monitors().is_empty());
+            if connected {
+                report.present.push(settings.adapter.clone());
+            } else {
+                report.missing.push(settings.adapter.clone());
+            }
+        }
+        report.extra = adapters
+            .iter()
+            .filter(|adapter| adapter.state.active())
+            .filter(|adapter| {
+                !self
+                    .displays
+                    .iter()
+                    .any(|settings| settings.adapter == adapter.name)
+            })
+            .map(|adapter| adapter.name.clone())
+            .collect();
+
+        report
+    }
+
+    /// Applies the profile to all displays at once.
+    ///
+    /// Adapters which aren't part of the profile are left as they are. Fails with
+    /// `SetDisplaySettingsError::BadParam` if one of the adapters in the profile doesn't exist.
+    /// Check `matches_current` first to avoid applying a profile to different hardware.
+    pub fn apply(&self) -> Result<(), SetDisplaySettingsError> {
+        let adapters = DisplayAdapters::new().ok_or(SetDisplaySettingsError::Failed)?;
+
+        for settings in &self.displays {
+            let adapter = adapters
+                .iter()
+                .find(|adapter| adapter.name == settings.adapter)
+                .ok_or(SetDisplaySettingsError::BadParam)?;
+
+            let mut devmode = adapter.settings_devmode(settings);
+            adapter
+                .change_settings(&mut devmode, CDS_NORESET | CDS_UPDATEREGISTRY)?
+                .into_result()?;
+        }
+
+        apply_pending()
+    }
+
+    /// Like `apply`, but restores the previous settings if any of the displays fails to apply.
+    ///
+    /// Every adapter in the profile is reset to its state from before, including those which were
+    /// turned off, so that changes which were already written to the registry aren't applied. The
+    /// error which caused the rollback is returned, even if the rollback itself fails.
+    pub fn apply_or_rollback(&self) -> Result<(), SetDisplaySettingsError> {
+        let before = SystemSnapshot::capture();
+        if before.adapters.is_empty() {
+            return Err(SetDisplaySettingsError::Failed);
+        }
+
+        let result = self.apply();
+        if result.is_err() && self.staged(&before).apply_diff(&before).is_err() {
+            log::warn!("failed to roll back to the previous display settings");
+        }
+
+        result
+    }
+
+    /// What `snapshot` would look like after `apply`, which only changes the adapters in the
+    /// profile.
+    fn staged(&self, snapshot: &SystemSnapshot) -> SystemSnapshot {
+        let mut snapshot = snapshot.clone();
+        for adapter in &mut snapshot.adapters {
+            if let Some(settings) = self
+                .displays
+                .iter()
+                .find(|settings| settings.adapter == adapter.name)
+            {
+                adapter.state.insert(DisplayState::ACTIVE);
+                adapter.mode = Some(settings.mode);
+                adapter.position = Some(settings.position);
+            }
+        }
+
+        snapshot
+    }
+
+    /// What `snapshot` would look like if exactly the displays of the profile were active, with
+    /// their settings from the profile.
+    fn overlay(&self, snapshot: &SystemSnapshot) -> SystemSnapshot {
+        let mut snapshot = self.staged(snapshot);
+        for adapter in &mut snapshot.adapters {
+            if !self
+                .displays
+                .iter()
+                .any(|settings| settings.adapter == adapter.name)
+            {
+                adapter.state.remove(DisplayState::ACTIVE);
+                adapter.mode = None;
+                adapter.position = None;
+            }
+        }
+
+        snapshot
+    }
+}
+
+/// Switches back and forth between two profiles, e.g. a docked and an undocked layout.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProfileToggle {
+    pub first: Profile,
+    pub second: Profile,
+}
+
+impl ProfileToggle {
+    pub fn new(first: Profile, second: Profile) -> Self {
+        Self { first, second }
+    }
+
+    /// Switches to whichever profile isn't currently active. If neither is, `first` is applied.
+    ///
+    /// Unlike `Profile::apply`, adapters which aren't part of the new profile are turned off, so
+    /// that the next toggle recognizes the profile as active.
+    pub fn toggle(&self) -> Result<(), SetDisplaySettingsError> {
+        let current = SystemSnapshot::capture();
+        let target = match self.active(&current) {
+            Some(active) if ptr::eq(active, &self.first) => &self.second,
+            _ => &self.first,
+        };
+
+        current.apply_diff(&target.overlay(&current))
+    }
+
+    /// The profile which is closer to `current`, or `None` if both are equally close.
+    ///
+    /// Closeness is the number of changes `SystemSnapshot::diff` finds between `current` and the
+    /// profile applied to it. Active adapters which aren't part of a profile count as changes, so
+    /// that a profile with only the laptop panel doesn't match a docked setup.
+    fn active(&self, current: &SystemSnapshot) -> Option<&Profile> {
+        let first = current.diff(&self.first.overlay(current)).len();
+        let second = current.diff(&self.second.overlay(current)).len();
+
+        match first.cmp(&second) {
+            Ordering::Less => Some(&self.first),
+            Ordering::Greater => Some(&self.second),
+            Ordering::Equal => None,
+        }
+    }
+}
+
+/// How a `Profile` relates to the connected displays, as returned by `Profile::matches_current`.
+/// Adapters are given by their `name`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MatchReport {
+    /// Displays in the profile which are connected.
+    pub present: Vec<String>,
+    /// Displays in the profile which aren't connected.
+    pub missing: Vec<String>,
+    /// Active displays which aren't part of the profile.
+    pub extra: Vec<String>,
+}
+
+impl MatchReport {
+    /// Whether the profile covers exactly the connected displays.
+    pub fn is_exact(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AdapterSnapshot, DisplayChange, DisplayOrientation};
+
+    fn mode(width: u32, height: u32) -> DisplayMode {
+        DisplayMode::new(width, height, 32, 60, DisplayOrientation::Default)
+    }
+
+    fn adapter(name: &str, settings: Option<(i32, DisplayMode)>) -> AdapterSnapshot {
+        AdapterSnapshot {
+            name: name.to_string(),
+            string: "Generic adapter".to_string(),
+            state: if settings.is_some() {
+                DisplayState::ACTIVE
+            } else {
+                DisplayState::empty()
+            },
+            id: r"PCI\VEN_8086".to_string(),
+            key: String::new(),
+            position: settings.map(|(x, _)| Point { x, y: 0 }),
+            mode: settings.map(|(_, mode)| mode),
+            monitors: Vec::new(),
+        }
+    }
+
+    fn settings(name: &str, x: i32, mode: DisplayMode) -> DisplaySettings {
+        DisplaySettings {
+            adapter: name.to_string(),
+            position: Point { x, y: 0 },
+            mode,
+        }
+    }
+
+    fn toggle() -> ProfileToggle {
+        let docked = Profile {
+            displays: vec![
+                settings("laptop", 0, mode(1920, 1080)),
+                settings("external", 1920, mode(2560, 1440)),
+            ],
+        };
+        let undocked = Profile {
+            displays: vec![settings("laptop", 0, mode(1920, 1080))],
+        };
+
+        ProfileToggle::new(docked, undocked)
+    }
+
+    #[test]
+    fn docked_is_active() {
+        let toggle = toggle();
+        let current = SystemSnapshot {
+            adapters: vec![
+                adapter("laptop", Some((0, mode(1920, 1080)))),
+                adapter("external", Some((1920, mode(2560, 1440)))),
+            ],
+        };
+
+        assert_eq!(toggle.active(&current), Some(&toggle.first));
+    }
+
+    #[test]
+    fn undocked_is_active() {
+        let toggle = toggle();
+        let current = SystemSnapshot {
+            adapters: vec![
+                adapter("laptop", Some((0, mode(1920, 1080)))),
+                adapter("external", None),
+            ],
+        };
+
+        assert_eq!(toggle.active(&current), Some(&toggle.second));
+    }
+
+    #[test]
+    fn closer_profile_is_active() {
+        // Docked, but the laptop panel runs at a different resolution than in either profile.
+        let toggle = toggle();
+        let current = SystemSnapshot {
+            adapters: vec![
+                adapter("laptop", Some((0, mode(1280, 720)))),
+                adapter("external", Some((1920, mode(2560, 1440)))),
+            ],
+        };
+
+        assert_eq!(toggle.active(&current), Some(&toggle.first));
+    }
+
+    #[test]
+    fn toggled_profile_is_active() {
+        let toggle = toggle();
+        let docked = SystemSnapshot {
+            adapters: vec![
+                adapter("laptop", Some((0, mode(1920, 1080)))),
+                adapter("external", Some((1920, mode(2560, 1440)))),
+            ],
+        };
+
+        let undocked = toggle.second.overlay(&docked);
+        assert_eq!(undocked.adapters[1], adapter("external", None));
+        assert_eq!(toggle.active(&undocked), Some(&toggle.second));
+
+        let docked = toggle.first.overlay(&undocked);
+        assert_eq!(toggle.active(&docked), Some(&toggle.first));
+    }
+
+    #[test]
+    fn rollback_turns_off_staged_adapters() {
+        let toggle = toggle();
+        let before = SystemSnapshot {
+            adapters: vec![
+                adapter("laptop", Some((0, mode(1920, 1080)))),
+                adapter("external", None),
+            ],
+        };
+
+        // Only "external" was off before, so it's the only adapter to reset.
+        let staged = toggle.first.staged(&before);
+        assert_eq!(
+            staged.diff(&before),
+            vec![DisplayChange::Detached("external".to_string())]
+        );
+    }
+
+    #[test]
+    fn tie_has_no_active_profile() {
+        // Both profiles are one resolution change away.
+        let toggle = ProfileToggle::new(
+            Profile {
+                displays: vec![settings("laptop", 0, mode(1920, 1080))],
+            },
+            Profile {
+                displays: vec![settings("laptop", 0, mode(1600, 900))],
+            },
+        );
+        let current = SystemSnapshot {
+            adapters: vec![adapter("laptop", Some((0, mode(1280, 720))))],
+        };
+
+        assert_eq!(toggle.active(&current), None);
+    }
+}
@@ -0,0 +1,122 @@
+//! Automatic screen rotation on devices with an orientation sensor.
+//!
+//! https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getautorotationstate
+
+use winapi::shared::minwindef::{BOOL, DWORD};
+
+// These aren't exposed by winapi 0.3
+#[allow(non_camel_case_types)]
+type AR_STATE = DWORD;
+#[allow(non_camel_case_types)]
+type ORIENTATION_PREFERENCE = DWORD;
+
+const AR_NOSENSOR: AR_STATE = 0x10;
+const AR_NOT_SUPPORTED: AR_STATE = 0x20;
+
+const ORIENTATION_PREFERENCE_NONE: ORIENTATION_PREFERENCE = 0x0;
+const ORIENTATION_PREFERENCE_LANDSCAPE: ORIENTATION_PREFERENCE = 0x1;
+const ORIENTATION_PREFERENCE_PORTRAIT: ORIENTATION_PREFERENCE = 0x2;
+const ORIENTATION_PREFERENCE_LANDSCAPE_FLIPPED: ORIENTATION_PREFERENCE = 0x4;
+const ORIENTATION_PREFERENCE_PORTRAIT_FLIPPED: ORIENTATION_PREFERENCE = 0x8;
+
+#[link(name = "user32")]
+extern "system" {
+    fn GetAutoRotationState(pState: *mut AR_STATE) -> BOOL;
+    fn GetDisplayAutoRotationPreferences(pOrientation: *mut ORIENTATION_PREFERENCE) -> BOOL;
+    fn SetDisplayAutoRotationPreferences(orientation: ORIENTATION_PREFERENCE) -> BOOL;
+}
+
+/// Mirrors `ORIENTATION_PREFERENCE`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OrientationPreference {
+    /// Rotate freely with the device.
+    None,
+    Landscape,
+    Portrait,
+    LandscapeFlipped,
+    PortraitFlipped,
+}
+
+impl OrientationPreference {
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            ORIENTATION_PREFERENCE_NONE => Some(Self::None),
+            ORIENTATION_PREFERENCE_LANDSCAPE => Some(Self::Landscape),
+            ORIENTATION_PREFERENCE_PORTRAIT => Some(Self::Portrait),
+            ORIENTATION_PREFERENCE_LANDSCAPE_FLIPPED => Some(Self::LandscapeFlipped),
+            ORIENTATION_PREFERENCE_PORTRAIT_FLIPPED => Some(Self::PortraitFlipped),
+            _ => None,
+        }
+    }
+
+    pub fn as_raw(self) -> u32 {
+        match self {
+            Self::None => ORIENTATION_PREFERENCE_NONE,
+            Self::Landscape => ORIENTATION_PREFERENCE_LANDSCAPE,
+            Self::Portrait => ORIENTATION_PREFERENCE_PORTRAIT,
+            Self::LandscapeFlipped => ORIENTATION_PREFERENCE_LANDSCAPE_FLIPPED,
+            Self::PortraitFlipped => ORIENTATION_PREFERENCE_PORTRAIT_FLIPPED,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AutoRotationError {
+    /// The device has no orientation sensor.
+    NoSensor,
+    /// The device doesn't support auto-rotation.
+    NotSupported,
+    Failed,
+}
+
+impl std::fmt::Display for AutoRotationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::NoSensor => "the device has no orientation sensor",
+            Self::NotSupported => "the device doesn't support auto-rotation",
+            Self::Failed => "failed to access the auto-rotation preferences",
+        })
+    }
+}
+
+impl std::error::Error for AutoRotationError {}
+
+/// The orientation the screen is locked to while the calling process is in the foreground.
+pub fn get_auto_rotation() -> Result<OrientationPreference, AutoRotationError> {
+    check_auto_rotation_state()?;
+
+    let mut preference = 0;
+    if unsafe { GetDisplayAutoRotationPreferences(&mut preference) } == 0 {
+        return Err(AutoRotationError::Failed);
+    }
+
+    // Combinations of orientations can't be represented, so treat them like a failure.
+    OrientationPreference::from_raw(preference).ok_or(AutoRotationError::Failed)
+}
+
+/// Locks the screen to an orientation while the calling process is in the foreground.
+/// `OrientationPreference::None` lets it rotate freely again.
+pub fn set_auto_rotation(preference: OrientationPreference) -> Result<(), AutoRotationError> {
+    check_auto_rotation_state()?;
+
+    if unsafe { SetDisplayAutoRotationPreferences(preference.as_raw()) } == 0 {
+        Err(AutoRotationError::Failed)
+    } else {
+        Ok(())
+    }
+}
+
+fn check_auto_rotation_state() -> Result<(), AutoRotationError> {
+    let mut state = 0;
+    if unsafe { GetAutoRotationState(&mut state) } == 0 {
+        return Err(AutoRotationError::Failed);
+    }
+
+    if state & AR_NOSENSOR != 0 {
+        Err(AutoRotationError::NoSensor)
+    } else if state & AR_NOT_SUPPORTED != 0 {
+        Err(AutoRotationError::NotSupported)
+    } else {
+        Ok(())
+    }
+}
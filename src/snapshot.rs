@@ -0,0 +1,520 @@
+use winapi::um::winuser::{CDS_NORESET, CDS_UPDATEREGISTRY};
+
+use crate::{
+    apply_pending, DisplayAdapter, DisplayAdapters, DisplayMode, DisplayOrientation,
+    DisplaySettings, DisplayState, Monitor, Point, SetDisplaySettingsError,
+};
+
+/// An owned copy of the state of all adapters and their monitors at one point in time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SystemSnapshot {
+    pub adapters: Vec<AdapterSnapshot>,
+}
+
+/// The state of a single adapter within a `SystemSnapshot`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdapterSnapshot {
+    pub name: String,
+    pub string: String,
+    pub state: DisplayState,
+    pub id: String,
+    pub key: String,
+    /// `None` if the adapter isn't active.
+    pub position: Option<Point>,
+    /// `None` if the adapter isn't active.
+    pub mode: Option<DisplayMode>,
+    pub monitors: Vec<MonitorSnapshot>,
+}
+
+/// The state of a single monitor within an `AdapterSnapshot`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MonitorSnapshot {
+    pub name: String,
+    pub string: String,
+    pub state: DisplayState,
+    pub id: String,
+    pub key: String,
+}
+
+impl SystemSnapshot {
+    /// Captures the current state of all adapters. If enumeration fails, the snapshot is empty.
+    pub fn capture() -> Self {
+        let adapters = DisplayAdapters::new()
+            .map(|adapters| adapters.iter().map(AdapterSnapshot::capture).collect())
+            .unwrap_or_default();
+
+        Self { adapters }
+    }
+
+    /// The changes which turn `self` into `other`.
+    ///
+    /// Adapters are matched by their `id` and `name` together, since all outputs of a GPU share
+    /// the same `id`. Changes are reported in the order of the adapters in `other`, followed by
+    /// removed adapters.
+    pub fn diff(&self, other: &SystemSnapshot) -> Vec<DisplayChange> {
+        let mut changes = Vec::new();
+
+        for new in &other.adapters {
+            let old = match self.find(new) {
+                Some(old) => old,
+                None => {
+                    changes.push(DisplayChange::AdapterAdded(new.name.clone()));
+                    continue;
+                }
+            };
+            let adapter = || new.name.clone();
+
+            match (old.state.active(), new.state.active()) {
+                (false, true) => changes.push(DisplayChange::Attached(adapter())),
+                (true, false) => changes.push(DisplayChange::Detached(adapter())),
+                _ => {}
+            }
+
+            if let (Some(old_mode), Some(new_mode)) = (old.mode, new.mode) {
+                if (old_mode.width, old_mode.height) != (new_mode.width, new_mode.height) {
+                    changes.push(DisplayChange::ResolutionChanged {
+                        adapter: adapter(),
+                        old: (old_mode.width, old_mode.height),
+                        new: (new_mode.width, new_mode.height),
+                    });
+                }
+                if old_mode.frequency != new_mode.frequency {
+                    changes.push(DisplayChange::FrequencyChanged {
+                        adapter: adapter(),
+                        old: old_mode.frequency,
+                        new: new_mode.frequency,
+                    });
+                }
+                if old_mode.bits_per_pel != new_mode.bits_per_pel {
+                    changes.push(DisplayChange::BitsPerPelChanged {
+                        adapter: adapter(),
+                        old: old_mode.bits_per_pel,
+                        new: new_mode.bits_per_pel,
+                    });
+                }
+                if old_mode.orientation != new_mode.orientation {
+                    changes.push(DisplayChange::OrientationChanged {
+                        adapter: adapter(),
+                        old: old_mode.orientation,
+                        new: new_mode.orientation,
+                    });
+                }
+            }
+
+            if let (Some(old_position), Some(new_position)) = (old.position, new.position) {
+                if old_position != new_position {
+                    changes.push(DisplayChange::Moved {
+                        adapter: adapter(),
+                        old: old_position,
+                        new: new_position,
+                    });
+                }
+            }
+        }
+
+        for old in &self.adapters {
+            if other.find(old).is_none() {
+                changes.push(DisplayChange::AdapterRemoved(old.name.clone()));
+            }
+        }
+
+        let old_primary = self.primary().map(|adapter| adapter.name.clone());
+        let new_primary = other.primary().map(|adapter| adapter.name.clone());
+        if old_primary != new_primary {
+            changes.push(DisplayChange::PrimaryChanged {
+                old: old_primary,
+                new: new_primary,
+            });
+        }
+
+        changes
+    }
+
+    /// Changes the settings of the adapters which differ between `self` and `target`, so that they
+    /// match `target`. `self` should be a recent capture of the live system.
+    ///
+    /// All changes are written to the registry first and applied at once, with the adapters which
+    /// are turned on written before those which are turned off. If nothing differs, no settings
+    /// are changed at all. Adapters which only exist in one of the snapshots are left alone, and
+    /// the primary display is only changed as far as positions imply it.
+    ///
+    /// Fails with `SetDisplaySettingsError::WouldDisableLastDisplay` if no display would be active
+    /// in `target`. If writing one of the changes fails, the adapters written before it are reset
+    /// to their settings in `self`, and nothing is applied.
+    pub fn apply_diff(&self, target: &SystemSnapshot) -> Result<(), SetDisplaySettingsError> {
+        let actions = self.actions(target)?;
+        if actions.is_empty() {
+            return Ok(());
+        }
+
+        // Look up all adapters before writing anything, so that a missing one leaves the registry
+        // untouched.
+        let adapters = DisplayAdapters::new().ok_or(SetDisplaySettingsError::Failed)?;
+        let actions = actions
+            .iter()
+            .map(|action| {
+                adapters
+                    .iter()
+                    .find(|adapter| adapter.name == action.adapter())
+                    .map(|adapter| (adapter, action))
+                    .ok_or(SetDisplaySettingsError::BadParam)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (i, &(adapter, action)) in actions.iter().enumerate() {
+            if let Err(err) = action.write(adapter) {
+                for &(adapter, _) in &actions[..i] {
+                    let restored = match self.adapters.iter().find(|a| a.name == adapter.name) {
+                        Some(snapshot) => Action::restore(snapshot).write(adapter),
+                        None => Err(SetDisplaySettingsError::BadParam),
+                    };
+                    if restored.is_err() {
+                        log::warn!(
+                            "failed to restore the registry settings of {}",
+                            adapter.name
+                        );
+                    }
+                }
+                return Err(err);
+            }
+        }
+
+        apply_pending()
+    }
+
+    /// The settings changes which `apply_diff` writes, in order.
+    ///
+    /// The guard against turning off the last display is checked against `target` rather than the
+    /// live system, since displays which are turned on by the same diff are only attached once
+    /// everything is applied.
+    fn actions(&self, target: &SystemSnapshot) -> Result<Vec<Action>, SetDisplaySettingsError> {
+        let changes = self.diff(target);
+        let changed: Vec<&str> = changes.iter().filter_map(DisplayChange::adapter).collect();
+
+        let mut actions = Vec::new();
+        let mut detaches = Vec::new();
+        for snapshot in &target.adapters {
+            if !changed.contains(&snapshot.name.as_str()) {
+                continue;
+            }
+
+            match (snapshot.state.active(), snapshot.mode, snapshot.position) {
+                (true, Some(mode), Some(position)) => {
+                    actions.push(Action::Apply(DisplaySettings {
+                        adapter: snapshot.name.clone(),
+                        position,
+                        mode,
+                    }))
+                }
+                (false, _, _) => detaches.push(Action::Detach(snapshot.name.clone())),
+                _ => {}
+            }
+        }
+
+        let any_active = target
+            .adapters
+            .iter()
+            .any(|adapter| adapter.state.active() && !adapter.state.mirroring_driver());
+        if !detaches.is_empty() && !any_active {
+            return Err(SetDisplaySettingsError::WouldDisableLastDisplay);
+        }
+
+        actions.extend(detaches);
+        Ok(actions)
+    }
+
+    fn find(&self, adapter: &AdapterSnapshot) -> Option<&AdapterSnapshot> {
+        self.adapters
+            .iter()
+            .find(|other| other.id == adapter.id && other.name == adapter.name)
+    }
+
+    fn primary(&self) -> Option<&AdapterSnapshot> {
+        self.adapters
+            .iter()
+            .find(|adapter| adapter.state.primary_device())
+    }
+}
+
+impl AdapterSnapshot {
+    fn capture(adapter: &DisplayAdapter) -> Self {
+        let mode = adapter.current_mode();
+        let position = if mode.is_some() {
+            adapter.info().position
+        } else {
+            None
+        };
+
+        Self {
+            name: adapter.name.clone(),
+            string: adapter.string.clone(),
+            state: adapter.state,
+            id: adapter.id.clone(),
+            key: adapter.key.clone(),
+            position,
+            mode,
+            monitors: adapter
+                .monitors()
+                .iter()
+                .map(MonitorSnapshot::capture)
+                .collect(),
+        }
+    }
+}
+
+impl MonitorSnapshot {
+    fn capture(monitor: &Monitor) -> Self {
+        Self {
+            name: monitor.name.clone(),
+            string: monitor.string.clone(),
+            state: monitor.state,
+            id: monitor.id.clone(),
+            key: monitor.key.clone(),
+        }
+    }
+}
+
+/// A settings change which `SystemSnapshot::apply_diff` writes to the registry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Action {
+    Apply(DisplaySettings),
+    Detach(String),
+}
+
+impl Action {
+    /// Turns the adapter back to its settings in `snapshot`.
+    fn restore(snapshot: &AdapterSnapshot) -> Self {
+        match (snapshot.state.active(), snapshot.mode, snapshot.position) {
+            (true, Some(mode), Some(position)) => Self::Apply(DisplaySettings {
+                adapter: snapshot.name.clone(),
+                position,
+                mode,
+            }),
+            _ => Self::Detach(snapshot.name.clone()),
+        }
+    }
+
+    fn adapter(&self) -> &str {
+        match self {
+            Self::Apply(settings) => &settings.adapter,
+            Self::Detach(adapter) => adapter,
+        }
+    }
+
+    /// Writes the change to the registry without applying it. The last-display guard has already
+    /// been checked by `SystemSnapshot::actions`.
+    fn write(&self, adapter: &DisplayAdapter) -> Result<(), SetDisplaySettingsError> {
+        let mut devmode = match self {
+            Self::Apply(settings) => adapter.settings_devmode(settings),
+            Self::Detach(_) => adapter.detach_devmode(),
+        };
+
+        adapter
+            .change_settings_unchecked(&mut devmode, CDS_NORESET | CDS_UPDATEREGISTRY)?
+            .into_result()
+    }
+}
+
+/// A single difference between two `SystemSnapshot`s, as returned by `SystemSnapshot::diff`.
+///
+/// Adapters are referred to by their `name`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DisplayChange {
+    AdapterAdded(String),
+    AdapterRemoved(String),
+    /// The adapter became part of the desktop.
+    Attached(String),
+    /// The adapter was removed from the desktop.
+    Detached(String),
+    ResolutionChanged {
+        adapter: String,
+        old: (u32, u32),
+        new: (u32, u32),
+    },
+    FrequencyChanged {
+        adapter: String,
+        old: u32,
+        new: u32,
+    },
+    BitsPerPelChanged {
+        adapter: String,
+        old: u32,
+        new: u32,
+    },
+    OrientationChanged {
+        adapter: String,
+        old: DisplayOrientation,
+        new: DisplayOrientation,
+    },
+    Moved {
+        adapter: String,
+        old: Point,
+        new: Point,
+    },
+    /// `None` if there was no primary display.
+    PrimaryChanged {
+        old: Option<String>,
+        new: Option<String>,
+    },
+}
+
+impl DisplayChange {
+    /// The name of the adapter whose settings changed, or `None` if the change isn't about the
+    /// settings of a single adapter which exists in both snapshots.
+    pub fn adapter(&self) -> Option<&str> {
+        match self {
+            Self::Attached(adapter)
+            | Self::Detached(adapter)
+            | Self::ResolutionChanged { adapter, .. }
+            | Self::FrequencyChanged { adapter, .. }
+            | Self::BitsPerPelChanged { adapter, .. }
+            | Self::OrientationChanged { adapter, .. }
+            | Self::Moved { adapter, .. } => Some(adapter),
+            Self::AdapterAdded(_) | Self::AdapterRemoved(_) | Self::PrimaryChanged { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGIN: Point = Point { x: 0, y: 0 };
+
+    fn mode(width: u32, height: u32) -> DisplayMode {
+        DisplayMode::new(width, height, 32, 60, DisplayOrientation::Default)
+    }
+
+    fn adapter(name: &str, settings: Option<(Point, DisplayMode)>) -> AdapterSnapshot {
+        let state = if settings.is_some() {
+            DisplayState::ACTIVE
+        } else {
+            DisplayState::empty()
+        };
+
+        AdapterSnapshot {
+            name: name.to_string(),
+            string: "Generic adapter".to_string(),
+            state,
+            id: r"PCI\VEN_10DE".to_string(),
+            key: String::new(),
+            position: settings.map(|(position, _)| position),
+            mode: settings.map(|(_, mode)| mode),
+            monitors: Vec::new(),
+        }
+    }
+
+    fn settings(name: &str, x: i32, mode: DisplayMode) -> DisplaySettings {
+        DisplaySettings {
+            adapter: name.to_string(),
+            position: Point { x, y: 0 },
+            mode,
+        }
+    }
+
+    #[test]
+    fn equal_snapshots_change_nothing() {
+        let snapshot = SystemSnapshot {
+            adapters: vec![
+                adapter("a", Some((ORIGIN, mode(1920, 1080)))),
+                adapter("b", Some((Point { x: 1920, y: 0 }, mode(1920, 1080)))),
+                adapter("c", None),
+            ],
+        };
+
+        assert_eq!(snapshot.actions(&snapshot.clone()), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn only_changed_adapters_are_written() {
+        let current = SystemSnapshot {
+            adapters: vec![
+                adapter("a", Some((ORIGIN, mode(1920, 1080)))),
+                adapter("b", Some((Point { x: 1920, y: 0 }, mode(1920, 1080)))),
+            ],
+        };
+        let target = SystemSnapshot {
+            adapters: vec![
+                adapter("a", Some((ORIGIN, mode(1920, 1080)))),
+                adapter("b", Some((Point { x: 1920, y: 0 }, mode(2560, 1440)))),
+            ],
+        };
+
+        assert_eq!(
+            current.actions(&target),
+            Ok(vec![Action::Apply(settings("b", 1920, mode(2560, 1440)))])
+        );
+    }
+
+    #[test]
+    fn swap_writes_attach_before_detach() {
+        let mut current = SystemSnapshot {
+            adapters: vec![
+                adapter("laptop", Some((ORIGIN, mode(1920, 1080)))),
+                adapter("external", None),
+            ],
+        };
+        current.adapters[0].state |= DisplayState::PRIMARY_DEVICE;
+        let mut target = SystemSnapshot {
+            adapters: vec![
+                adapter("laptop", None),
+                adapter("external", Some((ORIGIN, mode(1920, 1080)))),
+            ],
+        };
+        target.adapters[1].state |= DisplayState::PRIMARY_DEVICE;
+
+        assert_eq!(
+            current.actions(&target),
+            Ok(vec![
+                Action::Apply(settings("external", 0, mode(1920, 1080))),
+                Action::Detach("laptop".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn rollback_detaches_staged_attach() {
+        let before = SystemSnapshot {
+            adapters: vec![
+                adapter("laptop", Some((ORIGIN, mode(1920, 1080)))),
+                adapter("external", None),
+            ],
+        };
+        let staged = SystemSnapshot {
+            adapters: vec![
+                adapter("laptop", Some((ORIGIN, mode(1920, 1080)))),
+                adapter(
+                    "external",
+                    Some((Point { x: 1920, y: 0 }, mode(2560, 1440))),
+                ),
+            ],
+        };
+
+        assert_eq!(
+            staged.actions(&before),
+            Ok(vec![Action::Detach("external".to_string())])
+        );
+    }
+
+    #[test]
+    fn detaching_every_display_is_refused() {
+        let current = SystemSnapshot {
+            adapters: vec![
+                adapter("a", Some((ORIGIN, mode(1920, 1080)))),
+                adapter("b", None),
+            ],
+        };
+        let target = SystemSnapshot {
+            adapters: vec![adapter("a", None), adapter("b", None)],
+        };
+
+        assert_eq!(
+            current.actions(&target),
+            Err(SetDisplaySettingsError::WouldDisableLastDisplay)
+        );
+    }
+}
@@ -0,0 +1,407 @@
+use std::{
+    cell::{Cell, RefCell},
+    mem, ptr,
+    sync::{mpsc, Arc, Mutex, MutexGuard, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use winapi::{
+    shared::{
+        guiddef::GUID,
+        minwindef::{DWORD, HIWORD, LOWORD, LPARAM, LRESULT, UINT, WPARAM},
+        windef::HWND,
+    },
+    um::{
+        dbt::{
+            DBT_DEVICEARRIVAL, DBT_DEVICEREMOVECOMPLETE, DBT_DEVNODES_CHANGED,
+            DBT_DEVTYP_DEVICEINTERFACE, DEV_BROADCAST_DEVICEINTERFACE_W, DEV_BROADCAST_HDR,
+        },
+        libloaderapi::GetModuleHandleW,
+        processthreadsapi::GetCurrentThreadId,
+        winuser::{
+            CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetMessageW,
+            PostThreadMessageW, RegisterClassExW, RegisterDeviceNotificationW, TranslateMessage,
+            UnregisterDeviceNotification, DEVICE_NOTIFY_WINDOW_HANDLE, HDEVNOTIFY, MSG,
+            WM_DEVICECHANGE, WM_DISPLAYCHANGE, WM_QUIT, WNDCLASSEXW, WS_EX_TOOLWINDOW,
+            WS_OVERLAPPED,
+        },
+    },
+};
+
+use crate::{to_wide, SystemSnapshot};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DisplayChangeEvent {
+    /// `WM_DISPLAYCHANGE`: the resolution or color depth of the desktop changed.
+    DisplayChange {
+        bits_per_pixel: u32,
+        width: u32,
+        height: u32,
+    },
+    /// `WM_DEVICECHANGE` with `DBT_DEVNODES_CHANGED`: a device was added to or removed from the
+    /// system. This is not limited to monitors.
+    DevNodesChanged,
+    /// A monitor was physically connected. Carries the monitor's device interface path.
+    MonitorConnected(String),
+    /// A monitor was physically disconnected. Carries the monitor's device interface path.
+    MonitorDisconnected(String),
+    /// A burst of events has settled, when the watcher is debounced. Carries a snapshot taken
+    /// afterwards.
+    Settled(SystemSnapshot),
+}
+
+/// Listens for display changes on a background thread.
+///
+/// Dropping the watcher stops the thread.
+///
+/// The watcher's window is created on, and only ever touched by, the background thread. The watcher
+/// itself only refers to that thread by its ID, so it is `Send` and can be moved to and dropped on
+/// any thread.
+pub struct DisplayWatcher {
+    receiver: mpsc::Receiver<DisplayChangeEvent>,
+    thread_id: DWORD,
+    thread: Option<thread::JoinHandle<()>>,
+    debounce: Option<Duration>,
+    /// When the last event of a burst which hasn't settled yet arrived.
+    last_event: Cell<Option<Instant>>,
+}
+
+impl DisplayWatcher {
+    pub fn new() -> Option<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let (ready_sender, ready_receiver) = mpsc::sync_channel(1);
+
+        let thread = thread::spawn(move || {
+            let hwnd = match create_window() {
+                Some(hwnd) => hwnd,
+                None => {
+                    let _ = ready_sender.send(None);
+                    return;
+                }
+            };
+
+            let notify = register_monitor_notifications(hwnd);
+
+            SENDER.with(|s| *s.borrow_mut() = Some(sender));
+            let _ = ready_sender.send(Some(unsafe { GetCurrentThreadId() }));
+
+            let mut msg: MSG = unsafe { mem::zeroed() };
+            while unsafe { GetMessageW(&mut msg, ptr::null_mut(), 0, 0) } > 0 {
+                unsafe {
+                    TranslateMessage(&msg);
+                    DispatchMessageW(&msg);
+                }
+            }
+
+            if !notify.is_null() {
+                unsafe { UnregisterDeviceNotification(notify) };
+            }
+            unsafe { DestroyWindow(hwnd) };
+            SENDER.with(|s| *s.borrow_mut() = None);
+        });
+
+        match ready_receiver.recv() {
+            Ok(Some(thread_id)) => Some(Self {
+                receiver,
+                thread_id,
+                thread: Some(thread),
+                debounce: None,
+                last_event: Cell::new(None),
+            }),
+            _ => {
+                let _ = thread.join();
+                None
+            }
+        }
+    }
+
+    /// Coalesces bursts of events, such as those caused by connecting a dock, into a single
+    /// `DisplayChangeEvent::Settled`. It's emitted once no event has arrived for `window`.
+    pub fn with_debounce(mut self, window: Duration) -> Self {
+        self.debounce = Some(window);
+        self
+    }
+
+    /// Blocks until the next event arrives. When debounced, this blocks until a burst of events
+    /// has settled.
+    pub fn recv(&self) -> Option<DisplayChangeEvent> {
+        let window = match self.debounce {
+            Some(window) => window,
+            None => return self.receiver.recv().ok(),
+        };
+
+        let mut last_event = match self.last_event.get() {
+            Some(last_event) => last_event,
+            None => {
+                self.receiver.recv().ok()?;
+                Instant::now()
+            }
+        };
+        // Also stops if the window thread has gone away.
+        while let Some(remaining) = window.checked_sub(last_event.elapsed()) {
+            match self.receiver.recv_timeout(remaining) {
+                Ok(_) => last_event = Instant::now(),
+                Err(_) => break,
+            }
+        }
+
+        self.last_event.set(None);
+        Some(DisplayChangeEvent::Settled(SystemSnapshot::capture()))
+    }
+
+    /// Returns the next event if one has arrived, without blocking.
+    ///
+    /// When debounced, this returns `None` until no event has arrived for the debounce window, and
+    /// then returns the `DisplayChangeEvent::Settled` for the burst.
+    pub fn try_recv(&self) -> Option<DisplayChangeEvent> {
+        let window = match self.debounce {
+            Some(window) => window,
+            None => return self.receiver.try_recv().ok(),
+        };
+
+        while self.receiver.try_recv().is_ok() {
+            self.last_event.set(Some(Instant::now()));
+        }
+
+        match self.last_event.get() {
+            Some(last_event) if last_event.elapsed() >= window => {
+                self.last_event.set(None);
+                Some(DisplayChangeEvent::Settled(SystemSnapshot::capture()))
+            }
+            _ => None,
+        }
+    }
+
+    /// A blocking iterator over incoming events.
+    pub fn iter(&self) -> impl Iterator<Item = DisplayChangeEvent> + '_ {
+        std::iter::from_fn(move || self.recv())
+    }
+}
+
+impl Drop for DisplayWatcher {
+    fn drop(&mut self) {
+        unsafe { PostThreadMessageW(self.thread_id, WM_QUIT, 0, 0) };
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Returns a handle to a watcher which is shared by the whole process.
+///
+/// The watcher's thread is started by the first subscriber and stopped again once the last
+/// subscription is dropped.
+pub fn global_watcher() -> DisplayWatcherHandle {
+    DisplayWatcherHandle { _private: () }
+}
+
+type Callback = Arc<dyn Fn(&DisplayChangeEvent) + Send + Sync>;
+
+struct GlobalWatcher {
+    subscribers: Vec<(u64, Callback)>,
+    next_id: u64,
+    /// The ID of the running watcher's window thread.
+    thread_id: Option<DWORD>,
+}
+
+static GLOBAL_WATCHER: Mutex<GlobalWatcher> = Mutex::new(GlobalWatcher {
+    subscribers: Vec::new(),
+    next_id: 0,
+    thread_id: None,
+});
+
+/// The state stays consistent even if a thread panicked while holding the lock, so poisoning is
+/// ignored.
+fn lock_global_watcher() -> MutexGuard<'static, GlobalWatcher> {
+    GLOBAL_WATCHER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A handle to the process-wide watcher, as returned by `global_watcher`.
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayWatcherHandle {
+    _private: (),
+}
+
+impl DisplayWatcherHandle {
+    /// Registers a callback which is called with every event, until the returned subscription is
+    /// dropped. Returns `None` if the watcher couldn't be started.
+    ///
+    /// Callbacks are called one after another on a background thread, so they should return
+    /// quickly.
+    pub fn subscribe(
+        &self,
+        callback: impl Fn(&DisplayChangeEvent) + Send + Sync + 'static,
+    ) -> Option<Subscription> {
+        let mut global = lock_global_watcher();
+
+        if global.thread_id.is_none() {
+            let watcher = DisplayWatcher::new()?;
+            global.thread_id = Some(watcher.thread_id);
+            // The events stop once the window thread quits, which also ends this thread.
+            thread::spawn(move || {
+                for event in watcher.iter() {
+                    let subscribers: Vec<Callback> = lock_global_watcher()
+                        .subscribers
+                        .iter()
+                        .map(|(_, callback)| callback.clone())
+                        .collect();
+                    for callback in subscribers {
+                        callback(&event);
+                    }
+                }
+            });
+        }
+
+        let id = global.next_id;
+        global.next_id += 1;
+        global.subscribers.push((id, Arc::new(callback)));
+
+        Some(Subscription { id })
+    }
+}
+
+/// A callback registered with `DisplayWatcherHandle::subscribe`. Dropping it unregisters the
+/// callback.
+#[derive(Debug)]
+pub struct Subscription {
+    id: u64,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut global = lock_global_watcher();
+        global.subscribers.retain(|(id, _)| *id != self.id);
+
+        if global.subscribers.is_empty() {
+            if let Some(thread_id) = global.thread_id.take() {
+                unsafe { PostThreadMessageW(thread_id, WM_QUIT, 0, 0) };
+            }
+        }
+    }
+}
+
+thread_local! {
+    static SENDER: RefCell<Option<mpsc::Sender<DisplayChangeEvent>>> = const { RefCell::new(None) };
+}
+
+const CLASS_NAME: &str = "monman_display_watcher";
+
+// Message-only windows (`HWND_MESSAGE`) don't receive broadcast messages like `WM_DISPLAYCHANGE`,
+// so this is a regular top-level window that is simply never shown.
+fn create_window() -> Option<HWND> {
+    let class_name = to_wide(CLASS_NAME);
+    let hinstance = unsafe { GetModuleHandleW(ptr::null()) };
+
+    let mut class: WNDCLASSEXW = unsafe { mem::zeroed() };
+    class.cbSize = mem::size_of::<WNDCLASSEXW>() as u32;
+    class.lpfnWndProc = Some(window_proc);
+    class.hInstance = hinstance;
+    class.lpszClassName = class_name.as_ptr();
+
+    // This fails if the class has already been registered by another watcher, which is fine.
+    unsafe { RegisterClassExW(&class) };
+
+    let hwnd = unsafe {
+        CreateWindowExW(
+            WS_EX_TOOLWINDOW,
+            class_name.as_ptr(),
+            class_name.as_ptr(),
+            WS_OVERLAPPED,
+            0,
+            0,
+            0,
+            0,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            hinstance,
+            ptr::null_mut(),
+        )
+    };
+
+    if hwnd.is_null() {
+        None
+    } else {
+        Some(hwnd)
+    }
+}
+
+/// https://docs.microsoft.com/en-us/windows-hardware/drivers/install/guid-devinterface-monitor
+const GUID_DEVINTERFACE_MONITOR: GUID = GUID {
+    Data1: 0xe6f0_7b5f,
+    Data2: 0xee97,
+    Data3: 0x4a90,
+    Data4: [0xb0, 0x76, 0x33, 0xf5, 0x7b, 0xf4, 0xea, 0xa7],
+};
+
+fn register_monitor_notifications(hwnd: HWND) -> HDEVNOTIFY {
+    let mut filter: DEV_BROADCAST_DEVICEINTERFACE_W = unsafe { mem::zeroed() };
+    filter.dbcc_size = mem::size_of::<DEV_BROADCAST_DEVICEINTERFACE_W>() as u32;
+    filter.dbcc_devicetype = DBT_DEVTYP_DEVICEINTERFACE;
+    filter.dbcc_classguid = GUID_DEVINTERFACE_MONITOR;
+
+    unsafe {
+        RegisterDeviceNotificationW(
+            hwnd as _,
+            &mut filter as *mut _ as _,
+            DEVICE_NOTIFY_WINDOW_HANDLE,
+        )
+    }
+}
+
+/// Reads the interface path out of a `DEV_BROADCAST_DEVICEINTERFACE_W` if `lparam` points to one.
+unsafe fn device_interface_path(lparam: LPARAM) -> Option<String> {
+    let header = lparam as *const DEV_BROADCAST_HDR;
+    if header.is_null() || (*header).dbch_devicetype != DBT_DEVTYP_DEVICEINTERFACE {
+        return None;
+    }
+
+    let interface = lparam as *const DEV_BROADCAST_DEVICEINTERFACE_W;
+    let name = (*interface).dbcc_name.as_ptr();
+    let mut len = 0;
+    while *name.add(len) != 0 {
+        len += 1;
+    }
+
+    Some(String::from_utf16_lossy(std::slice::from_raw_parts(
+        name, len,
+    )))
+}
+
+unsafe extern "system" fn window_proc(
+    hwnd: HWND,
+    msg: UINT,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    let event = match msg {
+        WM_DISPLAYCHANGE => Some(DisplayChangeEvent::DisplayChange {
+            bits_per_pixel: wparam as u32,
+            width: LOWORD(lparam as u32) as u32,
+            height: HIWORD(lparam as u32) as u32,
+        }),
+        WM_DEVICECHANGE => match wparam {
+            DBT_DEVNODES_CHANGED => Some(DisplayChangeEvent::DevNodesChanged),
+            DBT_DEVICEARRIVAL => {
+                device_interface_path(lparam).map(DisplayChangeEvent::MonitorConnected)
+            }
+            DBT_DEVICEREMOVECOMPLETE => {
+                device_interface_path(lparam).map(DisplayChangeEvent::MonitorDisconnected)
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+
+    if let Some(event) = event {
+        SENDER.with(|s| {
+            if let Some(sender) = &*s.borrow() {
+                let _ = sender.send(event);
+            }
+        });
+    }
+
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}